@@ -1,9 +1,9 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use halo2curves::secq256k1::Secq256k1Affine;
+use halo2curves::CurveAffine;
 use poseidon_transcript::sponge::SpongeCurve;
 use poseidon_transcript::transcript::PoseidonTranscript;
-use r1cs_nark::r1cs::{R1CSNARKProver, R1CS};
-use r1cs_nark::MultiCommitGens;
+use r1cs_nark::r1cs::{setup, R1CSNARKProver, R1CSShape};
 
 fn criterion_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("r1cs-prove");
@@ -15,22 +15,63 @@ fn criterion_benchmark(c: &mut Criterion) {
         let num_input = 10;
 
         let prover_transcript = PoseidonTranscript::<C>::new(b"test-prove", SpongeCurve::K256);
-        let r1cs = R1CS::produce_synthetic_r1cs(num_cons, num_vars, num_input);
-        let gens = MultiCommitGens::new(num_cons, b"r1cs-nark");
+        let (shape, instance, witness) =
+            R1CSShape::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let (pk, _) = setup(&shape, b"r1cs-nark");
 
-        let mut prover = R1CSNARKProver::new(r1cs.clone(), prover_transcript, gens);
-
-        let witness = &r1cs.witness;
-        let public_input = &r1cs.public_input;
+        let mut prover = R1CSNARKProver::new(pk, prover_transcript);
 
         let name = format!("Prove {} constraints", num_cons);
         group.bench_function(name, move |b| {
             b.iter(|| {
-                prover.prove(black_box(witness), black_box(public_input));
+                prover
+                    .prove(black_box(&instance), black_box(&witness))
+                    .unwrap();
+            });
+        });
+    }
+}
+
+// Az, Bz, Cz and Ar, Br, Cr as the prover computes them,
+// one matrix at a time against the fused single pass
+fn matrix_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("r1cs-matrix-mul");
+    type C = Secq256k1Affine;
+    type F = <C as CurveAffine>::ScalarExt;
+
+    for s in &[10, 13, 15] {
+        let num_cons = 2usize.pow(*s);
+        let num_vars = num_cons;
+        let num_input = 10;
+
+        let (shape, _, _) = R1CSShape::<C>::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let num_cols = 1 + num_input + num_vars;
+        let z = (0..num_cols)
+            .map(|i| F::from(i as u64 + 1))
+            .collect::<Vec<F>>();
+        let r = (0..num_cols)
+            .map(|i| F::from(i as u64 + 2))
+            .collect::<Vec<F>>();
+
+        let name = format!("Separate {} constraints", num_cons);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for v in [&z, &r] {
+                    shape.A.mul_vector(black_box(v));
+                    shape.B.mul_vector(black_box(v));
+                    shape.C.mul_vector(black_box(v));
+                }
+            });
+        });
+
+        let name = format!("Fused {} constraints", num_cons);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                shape.multiply_vec_pair(black_box(&z), black_box(&r));
             });
         });
     }
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(benches, criterion_benchmark, matrix_benchmark);
 criterion_main!(benches);
@@ -1,13 +1,57 @@
 use crate::accumulation::hadamard::{
     HadamardAccProof, HadamardAccumulator, HadamardInstance, HadamardWitness,
 };
-use crate::{utils::hadamard_prod, CurveAffineExt, MultiCommitGens, PRNG};
+use crate::{utils::par_map, CurveAffineExt, MultiCommitGens, Transcript, PRNG};
 use ff::{Field, PrimeField, PrimeFieldBits};
 use halo2curves::FieldExt;
 use poseidon_transcript::transcript::PoseidonTranscript;
+use std::fmt;
 use zeroize::DefaultIsZeroes;
 
-pub struct HadamardAccProver<C>
+/// Why the Hadamard accumulation prover could not accumulate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HadamardAccProvingError {
+    /// Neither prior accumulators nor instances were given to accumulate.
+    NoInstances,
+    /// `qx` and `qw` do not have one witness per instance.
+    WitnessCountMismatch { instances: usize, witnesses: usize },
+    /// a or b of the witness at `index`, counting the prior accumulators first,
+    /// does not have the length of the commitment key.
+    InvalidWitnessLength {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for HadamardAccProvingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoInstances => write!(f, "no instances to accumulate"),
+            Self::WitnessCountMismatch {
+                instances,
+                witnesses,
+            } => write!(
+                f,
+                "expected one witness per instance, got {} instances and {} witnesses",
+                instances, witnesses
+            ),
+            Self::InvalidWitnessLength {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "expected witness vectors of length {} for witness {}, got {}",
+                expected, index, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HadamardAccProvingError {}
+
+pub struct HadamardAccProver<C, T = PoseidonTranscript<C>>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
@@ -15,49 +59,96 @@ where
     C::ScalarExt: DefaultIsZeroes,
 {
     gens: MultiCommitGens<C>,
-    transcript: PoseidonTranscript<C>,
-    prng: PRNG<C>,
+    transcript: T,
+    prng: PRNG<C, T>,
 }
 
-impl<C> HadamardAccProver<C>
+impl<C, T> HadamardAccProver<C, T>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::Base: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
 {
-    pub fn new(gens: MultiCommitGens<C>, transcript: PoseidonTranscript<C>) -> Self {
+    pub fn new(gens: MultiCommitGens<C>, transcript: T) -> Self {
+        let prng = PRNG::new(&transcript);
+        Self::with_prng(gens, transcript, prng)
+    }
+
+    pub fn with_prng(gens: MultiCommitGens<C>, transcript: T, prng: PRNG<C, T>) -> Self {
         Self {
             gens,
             transcript,
-            prng: PRNG::new(),
+            prng,
         }
     }
 
+    /// Accumulate the instances `qx` with witnesses `qw` together with the `prior_accs`
+    /// of earlier steps, which are accumulated first.
     pub fn prove_acc(
         &mut self,
+        prior_accs: &[HadamardAccumulator<C>],
         qx: &Vec<HadamardInstance<C>>,
         qw: &Vec<HadamardWitness<C>>,
-    ) -> (HadamardAccumulator<C>, HadamardAccProof<C>) {
+    ) -> Result<(HadamardAccumulator<C>, HadamardAccProof<C>), HadamardAccProvingError> {
+        if qx.len() != qw.len() {
+            return Err(HadamardAccProvingError::WitnessCountMismatch {
+                instances: qx.len(),
+                witnesses: qw.len(),
+            });
+        }
+
+        // An accumulator is an instance-witness pair of the Hadamard relation itself
+        let qx = &prior_accs
+            .iter()
+            .map(|acc| acc.qx.clone())
+            .chain(qx.iter().cloned())
+            .collect::<Vec<HadamardInstance<C>>>();
+        let qw = &prior_accs
+            .iter()
+            .map(|acc| acc.qw.clone())
+            .chain(qw.iter().cloned())
+            .collect::<Vec<HadamardWitness<C>>>();
+
         let n = qx.len();
-        let l = qw[0].a_vec.len();
+        if n == 0 {
+            return Err(HadamardAccProvingError::NoInstances);
+        }
+
+        let l = self.gens.G.len();
+        for (index, qw_i) in qw.iter().enumerate() {
+            for vec in [&qw_i.a_vec, &qw_i.b_vec] {
+                if vec.len() != l {
+                    return Err(HadamardAccProvingError::InvalidWitnessLength {
+                        index,
+                        expected: l,
+                        actual: vec.len(),
+                    });
+                }
+            }
+        }
+
+        // Each call derives its challenges on its own fork of the base transcript
+        let mut transcript = self.transcript.fork(b"hadamard-acc-step");
 
         // Absorb the accumulator instances
         for acc_inst in qx {
-            self.transcript.append_point(&acc_inst.0);
-            self.transcript.append_point(&acc_inst.1);
-            self.transcript.append_point(&acc_inst.2);
+            transcript.append_point(&acc_inst.0);
+            transcript.append_point(&acc_inst.1);
+            transcript.append_point(&acc_inst.2);
         }
 
-        let mu = self.transcript.squeeze(1)[0];
+        let mu = transcript.squeeze_challenge();
         let mut mu_powers = vec![];
         for i in 0..n {
             mu_powers.push(mu.pow(&[i as u64, 0, 0, 0]));
         }
 
-        let mut t_vecs = vec![Vec::with_capacity(l); 2 * n - 1];
-        for i in 0..l {
+        // The coordinates are independent: compute the product coefficients of each,
+        // then gather them into one vector per power
+        let coordinate_coeffs = par_map(l, |i| {
             let mut a_coeffs = vec![];
             let mut b_coeffs = vec![];
             for (j, qw_i) in qw.iter().enumerate() {
@@ -69,12 +160,16 @@ where
             let mut product_coeffs = vec![C::ScalarExt::zero(); 2 * n - 1];
             for (i, a_i) in a_coeffs.iter().enumerate() {
                 for (j, b_i) in b_coeffs.iter().enumerate() {
-                    product_coeffs[i + j] = *a_i * *b_i;
+                    product_coeffs[i + j] += *a_i * *b_i;
                 }
             }
+            product_coeffs
+        });
 
+        let mut t_vecs = vec![Vec::with_capacity(l); 2 * n - 1];
+        for product_coeffs in coordinate_coeffs {
             for i in 0..(2 * n - 1) {
-                t_vecs[i].push(product_coeffs[i].clone());
+                t_vecs[i].push(product_coeffs[i]);
             }
         }
 
@@ -95,7 +190,11 @@ where
             }
         }
 
-        let nu = self.transcript.squeeze(1)[0];
+        // Absorb the cross-term commitments
+        transcript.append_points(&comm_t_vecs_low);
+        transcript.append_points(&comm_t_vecs_high);
+
+        let nu = transcript.squeeze_challenge();
 
         let mut nu_powers = vec![];
         for i in 0..qx.len() {
@@ -128,8 +227,20 @@ where
         let c3 = c3_1 + c3_2 + c3_3;
 
         // a_1 * mu^0 * nu^0 + a_2 * mu^1 * nu^1
-        let mut a = vec![C::ScalarExt::zero(); l];
-        let mut b = vec![C::ScalarExt::zero(); l];
+        let a = par_map(l, |j| {
+            qw.iter()
+                .enumerate()
+                .fold(C::ScalarExt::zero(), |acc, (i, qw_i)| {
+                    acc + qw_i.a_vec[j] * &mu_powers[i] * &nu_powers[i]
+                })
+        });
+        let b = par_map(l, |j| {
+            qw.iter()
+                .enumerate()
+                .fold(C::ScalarExt::zero(), |acc, (i, qw_i)| {
+                    acc + qw_i.b_vec[j] * &nu_powers[n - i - 1]
+                })
+        });
 
         // qw_1.w_1 * mu^0 * nu^0 + qw_1.w_2 * mu^1 * nu^1
         let mut w1 = C::ScalarExt::zero();
@@ -137,14 +248,7 @@ where
         let mut w3 = C::ScalarExt::zero();
 
         for (i, qw_i) in qw.iter().enumerate() {
-            for j in 0..qw_i.a_vec.len() {
-                a[j] += qw_i.a_vec[j] * &mu_powers[i] * &nu_powers[i];
-            }
             w1 += qw_i.w1 * &mu_powers[i] * &nu_powers[i];
-
-            for j in 0..qw_i.b_vec.len() {
-                b[j] += qw_i.b_vec[j] * &nu_powers[n - i - 1];
-            }
             w2 += qw_i.w2 * &nu_powers[n - i - 1];
 
             w3 += qw_i.w3 * &mu_powers[i];
@@ -155,7 +259,7 @@ where
         comm_t_vecs.extend_from_slice(&comm_t_vecs_low);
         comm_t_vecs.extend_from_slice(&comm_t_vecs_high);
 
-        (
+        Ok((
             HadamardAccumulator {
                 qx: HadamardInstance(c1.into(), c2.into(), c3.into()),
                 qw: HadamardWitness {
@@ -167,6 +271,6 @@ where
                 },
             },
             HadamardAccProof(comm_t_vecs),
-        )
+        ))
     }
 }
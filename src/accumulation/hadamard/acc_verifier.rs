@@ -1,14 +1,47 @@
 use crate::accumulation::hadamard::{
     HadamardAccProof, HadamardAccumulator, HadamardInstance, HadamardWitness,
 };
-use crate::{CurveAffineExt, MultiCommitGens};
+use crate::{CurveAffineExt, MultiCommitGens, Transcript};
 use ff::{Field, PrimeField, PrimeFieldBits};
 use halo2curves::FieldExt;
-use poseidon_transcript::sponge::SpongeCurve;
 use poseidon_transcript::transcript::PoseidonTranscript;
+use std::fmt;
 use zeroize::DefaultIsZeroes;
 
-pub struct HadamardAccVerifier<C>
+/// The check of the Hadamard accumulation verifier that an accumulation step failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HadamardAccVerificationError {
+    /// No instances were given to accumulate.
+    NoInstances,
+    /// The proof does not hold 2(n - 1) cross-term commitments.
+    InvalidProofLength {
+        expected: usize,
+        actual: usize,
+    },
+    C1Mismatch,
+    C2Mismatch,
+    C3Mismatch,
+}
+
+impl fmt::Display for HadamardAccVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoInstances => write!(f, "no instances to accumulate"),
+            Self::InvalidProofLength { expected, actual } => write!(
+                f,
+                "expected {} cross-term commitments, got {}",
+                expected, actual
+            ),
+            Self::C1Mismatch => write!(f, "folded commitment to a does not match"),
+            Self::C2Mismatch => write!(f, "folded commitment to b does not match"),
+            Self::C3Mismatch => write!(f, "folded commitment to a o b does not match"),
+        }
+    }
+}
+
+impl std::error::Error for HadamardAccVerificationError {}
+
+pub struct HadamardAccVerifier<C, T = PoseidonTranscript<C>>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
@@ -16,38 +49,64 @@ where
     C::Base: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
 {
-    transcript: PoseidonTranscript<C>,
+    transcript: T,
 }
 
-impl<C> HadamardAccVerifier<C>
+impl<C, T> HadamardAccVerifier<C, T>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::Base: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
 {
-    pub fn new(transcript: PoseidonTranscript<C>) -> Self {
+    pub fn new(transcript: T) -> Self {
         Self { transcript }
     }
 
+    /// Check that `acc_instance` accumulates the `prior_acc_instances` of earlier steps
+    /// followed by `accumulated_instances`.
     pub fn verify(
-        &mut self,
+        &self,
         acc_instance: &HadamardInstance<C>,
+        prior_acc_instances: &[HadamardInstance<C>],
         accumulated_instances: &[HadamardInstance<C>],
         proof: &HadamardAccProof<C>,
-    ) {
+    ) -> Result<(), HadamardAccVerificationError> {
+        let accumulated_instances = &prior_acc_instances
+            .iter()
+            .chain(accumulated_instances.iter())
+            .cloned()
+            .collect::<Vec<HadamardInstance<C>>>();
+
         let n = accumulated_instances.len();
+        if n == 0 {
+            return Err(HadamardAccVerificationError::NoInstances);
+        }
+        if proof.0.len() != 2 * (n - 1) {
+            return Err(HadamardAccVerificationError::InvalidProofLength {
+                expected: 2 * (n - 1),
+                actual: proof.0.len(),
+            });
+        }
+
+        // Replay the call's own fork of the base transcript, as the prover derived it
+        let mut transcript = self.transcript.fork(b"hadamard-acc-step");
 
         // Absorb the accumulator instances
         for acc_inst in accumulated_instances {
-            self.transcript.append_point(&acc_inst.0);
-            self.transcript.append_point(&acc_inst.1);
-            self.transcript.append_point(&acc_inst.2);
+            transcript.append_point(&acc_inst.0);
+            transcript.append_point(&acc_inst.1);
+            transcript.append_point(&acc_inst.2);
         }
 
-        let mu: C::ScalarExt = self.transcript.squeeze(1)[0];
-        let nu: C::ScalarExt = self.transcript.squeeze(1)[0];
+        let mu: C::ScalarExt = transcript.squeeze_challenge();
+
+        // Absorb the cross-term commitments
+        transcript.append_points(&proof.0);
+
+        let nu: C::ScalarExt = transcript.squeeze_challenge();
 
         let mut mu_powers = vec![];
         for i in 0..n {
@@ -85,8 +144,16 @@ where
 
         let expected_c3 = expected_c3_1 + expected_c3_2 + expected_c3_3;
 
-        assert_eq!(expected_c1, acc_instance.0.into());
-        assert_eq!(expected_c2, acc_instance.1.into());
-        assert_eq!(expected_c3, acc_instance.2.into());
+        if expected_c1 != acc_instance.0.into() {
+            return Err(HadamardAccVerificationError::C1Mismatch);
+        }
+        if expected_c2 != acc_instance.1.into() {
+            return Err(HadamardAccVerificationError::C2Mismatch);
+        }
+        if expected_c3 != acc_instance.2.into() {
+            return Err(HadamardAccVerificationError::C3Mismatch);
+        }
+
+        Ok(())
     }
 }
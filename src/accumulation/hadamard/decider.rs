@@ -0,0 +1,84 @@
+use crate::accumulation::hadamard::{HadamardAccumulator, HadamardInstance, HadamardWitness};
+use crate::{utils::hadamard_prod, CurveAffineExt, MultiCommitGens};
+use ff::{PrimeField, PrimeFieldBits};
+use std::fmt;
+use zeroize::DefaultIsZeroes;
+
+/// The check of the Hadamard decider that an accumulator failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HadamardDecisionError {
+    /// a or b does not have the length of the commitment key.
+    InvalidLength,
+    /// cm(a; w1) != qx.0
+    C1Mismatch,
+    /// cm(b; w2) != qx.1
+    C2Mismatch,
+    /// cm(a o b; w3) != qx.2
+    C3Mismatch,
+}
+
+impl fmt::Display for HadamardDecisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength => write!(f, "invalid witness vector length"),
+            Self::C1Mismatch => write!(f, "commitment to a does not match"),
+            Self::C2Mismatch => write!(f, "commitment to b does not match"),
+            Self::C3Mismatch => write!(f, "commitment to a o b does not match"),
+        }
+    }
+}
+
+impl std::error::Error for HadamardDecisionError {}
+
+pub struct HadamardDecider<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    gens: MultiCommitGens<C>,
+}
+
+impl<C> HadamardDecider<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub fn new(gens: MultiCommitGens<C>) -> Self {
+        Self { gens }
+    }
+
+    pub fn decide(&self, acc: &HadamardAccumulator<C>) -> Result<(), HadamardDecisionError> {
+        self.decide_parts(&acc.qx, &acc.qw)
+    }
+
+    // The R1CS accumulator keeps the Hadamard instance and witness apart,
+    // so its decider checks them without building a HadamardAccumulator.
+    pub(crate) fn decide_parts(
+        &self,
+        qx: &HadamardInstance<C>,
+        qw: &HadamardWitness<C>,
+    ) -> Result<(), HadamardDecisionError> {
+        if qw.a_vec.len() != self.gens.G.len() || qw.b_vec.len() != self.gens.G.len() {
+            return Err(HadamardDecisionError::InvalidLength);
+        }
+
+        if self.gens.commit(&qw.a_vec, &qw.w1) != qx.0 {
+            return Err(HadamardDecisionError::C1Mismatch);
+        }
+        if self.gens.commit(&qw.b_vec, &qw.w2) != qx.1 {
+            return Err(HadamardDecisionError::C2Mismatch);
+        }
+
+        let c = hadamard_prod::<C>(&qw.a_vec, &qw.b_vec);
+        if self.gens.commit(&c, &qw.w3) != qx.2 {
+            return Err(HadamardDecisionError::C3Mismatch);
+        }
+
+        Ok(())
+    }
+}
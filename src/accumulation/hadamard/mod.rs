@@ -1,5 +1,6 @@
 pub mod acc_prover;
 pub mod acc_verifier;
+pub mod decider;
 
 use crate::{utils::hadamard_prod, CurveAffineExt, MultiCommitGens, PRNG};
 use ff::{Field, PrimeField, PrimeFieldBits};
@@ -7,7 +8,7 @@ use halo2curves::FieldExt;
 use poseidon_transcript::transcript::PoseidonTranscript;
 use zeroize::DefaultIsZeroes;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HadamardInstance<C>(pub C, pub C, pub C)
 where
     C: CurveAffineExt,
@@ -30,6 +31,7 @@ where
     pub w3: C::ScalarExt,
 }
 
+#[derive(Debug, Clone)]
 pub struct HadamardAccumulator<C>
 where
     C: CurveAffineExt,
@@ -42,7 +44,7 @@ where
     pub qw: HadamardWitness<C>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HadamardAccProof<C>(Vec<C>)
 where
     C: CurveAffineExt,
@@ -50,11 +52,35 @@ where
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes;
 
+impl<C> HadamardAccProof<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub fn new(comm_t_vecs: Vec<C>) -> Self {
+        Self(comm_t_vecs)
+    }
+
+    /// Commitments to the cross terms of the product polynomial,
+    /// the n - 1 low coefficients followed by the n - 1 high ones.
+    pub fn comm_t_vecs(&self) -> &[C] {
+        &self.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{acc_prover::HadamardAccProver, acc_verifier::HadamardAccVerifier, *};
+    use super::{
+        acc_prover::{HadamardAccProver, HadamardAccProvingError},
+        acc_verifier::HadamardAccVerifier,
+        decider::{HadamardDecider, HadamardDecisionError},
+        *,
+    };
     use crate::predicates::hadamard::hadamard::HadamardProver;
     use crate::MultiCommitGens;
+    use ff::Field;
     use halo2curves::secq256k1::Secq256k1Affine;
     use halo2curves::CurveAffine;
     use poseidon_transcript::sponge::SpongeCurve;
@@ -78,7 +104,9 @@ mod tests {
             }
         }
 
-        let mut hadamard_prover = HadamardProver::new(gens.clone());
+        let blinder_transcript =
+            PoseidonTranscript::new(b"test_hadamard_blinders", SpongeCurve::K256);
+        let mut hadamard_prover = HadamardProver::new(gens.clone(), &blinder_transcript);
         let mut hadamard_instances = Vec::with_capacity(n);
         let mut hadamard_witnesses = Vec::with_capacity(n);
 
@@ -101,12 +129,282 @@ mod tests {
             PoseidonTranscript::new(b"test_hadamard_accumulation", SpongeCurve::K256);
         let mut acc_prover = HadamardAccProver::new(gens.clone(), acc_prover_transcript);
 
-        let (acc, acc_proof) = acc_prover.prove_acc(&hadamard_instances, &hadamard_witnesses);
+        let (acc, acc_proof) = acc_prover
+            .prove_acc(&[], &hadamard_instances, &hadamard_witnesses)
+            .unwrap();
 
         let acc_verifier_transcript =
             PoseidonTranscript::new(b"test_hadamard_accumulation", SpongeCurve::K256);
 
-        let mut acc_verifier = HadamardAccVerifier::new(acc_verifier_transcript);
-        acc_verifier.verify(&acc.qx, &hadamard_instances, &acc_proof);
+        let acc_verifier = HadamardAccVerifier::new(acc_verifier_transcript);
+        assert_eq!(
+            acc_verifier.verify(&acc.qx, &[], &hadamard_instances, &acc_proof),
+            Ok(())
+        );
+
+        let decider = HadamardDecider::new(gens.clone());
+        assert_eq!(decider.decide(&acc), Ok(()));
+
+        // Malformed inputs are rejected instead of panicking
+        assert_eq!(
+            acc_prover.prove_acc(&[], &vec![], &vec![]).err(),
+            Some(HadamardAccProvingError::NoInstances)
+        );
+        assert_eq!(
+            acc_prover
+                .prove_acc(&[], &hadamard_instances, &hadamard_witnesses[1..].to_vec())
+                .err(),
+            Some(HadamardAccProvingError::WitnessCountMismatch {
+                instances: n,
+                witnesses: n - 1
+            })
+        );
+
+        let mut short_witnesses = hadamard_witnesses.clone();
+        short_witnesses[2].b_vec.pop();
+        assert_eq!(
+            acc_prover
+                .prove_acc(&[], &hadamard_instances, &short_witnesses)
+                .err(),
+            Some(HadamardAccProvingError::InvalidWitnessLength {
+                index: 2,
+                expected: l,
+                actual: l - 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_hadamard_decider() {
+        type C = Secq256k1Affine;
+        type F = <C as CurveAffine>::ScalarExt;
+
+        let n = 4; // Number of proofs to accumulate
+        let l = 6; // Size of the vector
+
+        let gens = MultiCommitGens::<C>::new(l, b"test_hadamard_decider");
+        let blinder_transcript =
+            PoseidonTranscript::new(b"test_hadamard_blinders", SpongeCurve::K256);
+        let mut hadamard_prover = HadamardProver::new(gens.clone(), &blinder_transcript);
+
+        let mut hadamard_instances = Vec::with_capacity(n);
+        let mut hadamard_witnesses = Vec::with_capacity(n);
+        for i in 0..n {
+            let a = (0..l)
+                .map(|j| F::from_u128((i * l + j) as u128))
+                .collect::<Vec<F>>();
+            let b = (0..l)
+                .map(|j| F::from_u128((i + j + 1) as u128))
+                .collect::<Vec<F>>();
+
+            let proof = hadamard_prover.prove(&a, &b);
+            hadamard_instances.push(HadamardInstance(proof.c1, proof.c2, proof.c3));
+            hadamard_witnesses.push(HadamardWitness {
+                a_vec: a,
+                b_vec: b,
+                w1: proof.w1,
+                w2: proof.w2,
+                w3: proof.w3,
+            });
+        }
+
+        let decider = HadamardDecider::new(gens.clone());
+
+        // A single proof is itself a Hadamard accumulator
+        let single = HadamardAccumulator {
+            qx: HadamardInstance(
+                hadamard_instances[0].0,
+                hadamard_instances[0].1,
+                hadamard_instances[0].2,
+            ),
+            qw: hadamard_witnesses[0].clone(),
+        };
+        assert_eq!(decider.decide(&single), Ok(()));
+
+        let acc_prover_transcript =
+            PoseidonTranscript::new(b"test_hadamard_decider", SpongeCurve::K256);
+        let mut acc_prover = HadamardAccProver::new(gens.clone(), acc_prover_transcript);
+        let (acc, _) = acc_prover
+            .prove_acc(&[], &hadamard_instances, &hadamard_witnesses)
+            .unwrap();
+
+        assert_eq!(decider.decide(&acc), Ok(()));
+
+        let mut tampered = HadamardAccumulator {
+            qx: HadamardInstance(acc.qx.0, acc.qx.1, acc.qx.2),
+            qw: acc.qw.clone(),
+        };
+        tampered.qw.w3 += F::one();
+        assert_eq!(
+            decider.decide(&tampered),
+            Err(HadamardDecisionError::C3Mismatch)
+        );
+
+        tampered.qw = acc.qw.clone();
+        tampered.qw.b_vec[l - 1] += F::one();
+        assert_eq!(
+            decider.decide(&tampered),
+            Err(HadamardDecisionError::C2Mismatch)
+        );
+
+        tampered.qw = acc.qw.clone();
+        tampered.qw.a_vec.pop();
+        assert_eq!(
+            decider.decide(&tampered),
+            Err(HadamardDecisionError::InvalidLength)
+        );
+
+        // Shorter than the commitment key, though of the same length
+        tampered.qw.b_vec.pop();
+        assert_eq!(
+            decider.decide(&tampered),
+            Err(HadamardDecisionError::InvalidLength)
+        );
+
+        // Witness vectors swapped between two accumulated proofs
+        let mut swapped_witnesses = hadamard_witnesses.clone();
+        swapped_witnesses.swap(0, 1);
+        let acc_prover_transcript =
+            PoseidonTranscript::new(b"test_hadamard_decider", SpongeCurve::K256);
+        let mut acc_prover = HadamardAccProver::new(gens.clone(), acc_prover_transcript);
+        let (bad_acc, _) = acc_prover
+            .prove_acc(&[], &hadamard_instances, &swapped_witnesses)
+            .unwrap();
+        assert!(decider.decide(&bad_acc).is_err());
+    }
+
+    #[test]
+    fn test_hadamard_accumulation_with_prior_accumulator() {
+        type C = Secq256k1Affine;
+        type F = <C as CurveAffine>::ScalarExt;
+
+        let l = 5; // Size of the vector
+
+        let gens = MultiCommitGens::<C>::new(l, b"test_hadamard_multi_step");
+        let blinder_transcript =
+            PoseidonTranscript::new(b"test_hadamard_blinders", SpongeCurve::K256);
+        let mut hadamard_prover = HadamardProver::new(gens.clone(), &blinder_transcript);
+
+        let mut prove = |seed: usize| {
+            let a = (0..l)
+                .map(|j| F::from_u128((seed + j) as u128))
+                .collect::<Vec<F>>();
+            let b = (0..l)
+                .map(|j| F::from_u128((seed * j + 1) as u128))
+                .collect::<Vec<F>>();
+            let proof = hadamard_prover.prove(&a, &b);
+            (
+                HadamardInstance(proof.c1, proof.c2, proof.c3),
+                HadamardWitness {
+                    a_vec: a,
+                    b_vec: b,
+                    w1: proof.w1,
+                    w2: proof.w2,
+                    w3: proof.w3,
+                },
+            )
+        };
+
+        let (qx_1, qw_1): (Vec<_>, Vec<_>) = (0..2).map(&mut prove).unzip();
+        let (qx_2, qw_2): (Vec<_>, Vec<_>) = (2..4).map(&mut prove).unzip();
+
+        let decider = HadamardDecider::new(gens.clone());
+
+        let mut acc_prover = HadamardAccProver::new(
+            gens.clone(),
+            PoseidonTranscript::new(b"test_hadamard_multi_step", SpongeCurve::K256),
+        );
+        let acc_verifier = HadamardAccVerifier::new(PoseidonTranscript::new(
+            b"test_hadamard_multi_step",
+            SpongeCurve::K256,
+        ));
+
+        let (acc_1, acc_proof_1) = acc_prover.prove_acc(&[], &qx_1, &qw_1).unwrap();
+        assert_eq!(
+            acc_verifier.verify(&acc_1.qx, &[], &qx_1, &acc_proof_1),
+            Ok(())
+        );
+
+        let (acc_2, acc_proof_2) = acc_prover
+            .prove_acc(&[acc_1.clone()], &qx_2, &qw_2)
+            .unwrap();
+        assert_eq!(
+            acc_verifier.verify(&acc_2.qx, &[acc_1.qx.clone()], &qx_2, &acc_proof_2),
+            Ok(())
+        );
+        assert_eq!(decider.decide(&acc_2), Ok(()));
+
+        // The prior accumulator must be the one that was accumulated
+        let acc_verifier = HadamardAccVerifier::new(PoseidonTranscript::new(
+            b"test_hadamard_multi_step",
+            SpongeCurve::K256,
+        ));
+        acc_verifier
+            .verify(&acc_1.qx, &[], &qx_1, &acc_proof_1)
+            .unwrap();
+        assert!(acc_verifier
+            .verify(&acc_2.qx, &[qx_1[0].clone()], &qx_2, &acc_proof_2)
+            .is_err());
+    }
+
+    #[test]
+    fn test_hadamard_accumulation_cross_terms() {
+        type C = Secq256k1Affine;
+        type F = <C as CurveAffine>::ScalarExt;
+
+        let l = 4; // Size of the vector
+
+        let gens = MultiCommitGens::<C>::new(l, b"test_hadamard_cross_terms");
+        let blinder_transcript =
+            PoseidonTranscript::new(b"test_hadamard_blinders", SpongeCurve::K256);
+        let mut hadamard_prover = HadamardProver::new(gens.clone(), &blinder_transcript);
+
+        // From three proofs on, several products a_i o b_j add up to the same power
+        // of the product polynomial, and each of them has to be summed into its cross term
+        for n in 2..=5 {
+            let (qx, qw): (Vec<_>, Vec<_>) = (0..n)
+                .map(|i| {
+                    let a = (0..l)
+                        .map(|j| F::from_u128((7 * i + j + 1) as u128))
+                        .collect::<Vec<F>>();
+                    let b = (0..l)
+                        .map(|j| F::from_u128((3 * i * j + i + 2) as u128))
+                        .collect::<Vec<F>>();
+                    let proof = hadamard_prover.prove(&a, &b);
+                    (
+                        HadamardInstance(proof.c1, proof.c2, proof.c3),
+                        HadamardWitness {
+                            a_vec: a,
+                            b_vec: b,
+                            w1: proof.w1,
+                            w2: proof.w2,
+                            w3: proof.w3,
+                        },
+                    )
+                })
+                .unzip();
+
+            let mut acc_prover = HadamardAccProver::new(
+                gens.clone(),
+                PoseidonTranscript::new(b"test_hadamard_cross_terms", SpongeCurve::K256),
+            );
+            let (acc, acc_proof) = acc_prover.prove_acc(&[], &qx, &qw).unwrap();
+            assert_eq!(acc_proof.0.len(), 2 * (n - 1));
+
+            let acc_verifier = HadamardAccVerifier::new(PoseidonTranscript::new(
+                b"test_hadamard_cross_terms",
+                SpongeCurve::K256,
+            ));
+            assert_eq!(acc_verifier.verify(&acc.qx, &[], &qx, &acc_proof), Ok(()));
+
+            // The accumulated witness still opens to a Hadamard product
+            let qw = &acc.qw;
+            assert_eq!(gens.commit(&qw.a_vec, &qw.w1), acc.qx.0);
+            assert_eq!(gens.commit(&qw.b_vec, &qw.w2), acc.qx.1);
+            assert_eq!(
+                gens.commit(&hadamard_prod::<C>(&qw.a_vec, &qw.b_vec), &qw.w3),
+                acc.qx.2
+            );
+        }
     }
 }
@@ -1,2 +1,3 @@
-mod hadamard;
-mod r1cs;
+//! Split accumulation schemes for the Hadamard product relation and for the R1CS NARK.
+pub mod hadamard;
+pub mod r1cs;
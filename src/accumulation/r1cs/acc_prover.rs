@@ -1,15 +1,99 @@
-use super::utils::pi_1_to_hadamard_instance;
-use crate::accumulation::hadamard::acc_prover::HadamardAccProver;
-use crate::accumulation::hadamard::{HadamardAccProof, HadamardInstance, HadamardWitness};
-use crate::r1cs::{Pi1, R1CSNARKProof, R1CS};
-use crate::{CurveAffineExt, MultiCommitGens};
+use super::decider::{check_witness_lengths, R1CSAccDecisionError};
+use super::utils::{
+    append_acc_statement, commit_public_inputs, compute_beta, compute_gamma, fold_commitments,
+    fold_constant, pi_1_to_hadamard_instance,
+};
+use crate::accumulation::hadamard::acc_prover::{HadamardAccProver, HadamardAccProvingError};
+use crate::accumulation::hadamard::{
+    HadamardAccProof, HadamardAccumulator, HadamardInstance, HadamardWitness,
+};
+use crate::r1cs::{z_vector, Pi1, ProvingKey, R1CSNARKProof};
+use crate::{utils::par_map, CurveAffineExt, Transcript};
 use ff::{Field, PrimeField, PrimeFieldBits};
 use halo2curves::FieldExt;
-use poseidon_transcript::sponge::SpongeCurve;
 use poseidon_transcript::transcript::PoseidonTranscript;
+use std::fmt;
 use zeroize::DefaultIsZeroes;
 
-#[derive(Debug)]
+/// Why the R1CS accumulation prover could not accumulate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum R1CSAccProvingError {
+    /// Neither prior accumulators nor proofs were given to accumulate.
+    NoInstances,
+    /// The public input of the proof at `index` does not have `num_input` entries.
+    InvalidPublicInputLength {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// `s` of the proof at `index` does not have one entry per witness variable.
+    InvalidSLength {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// The prior accumulator at `index` does not fit the shape of the proving key.
+    InvalidAccumulator {
+        index: usize,
+        error: R1CSAccDecisionError,
+    },
+    /// The Hadamard accumulation prover could not accumulate the Hadamard instances.
+    Hadamard(HadamardAccProvingError),
+}
+
+impl fmt::Display for R1CSAccProvingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoInstances => write!(f, "no instances to accumulate"),
+            Self::InvalidPublicInputLength {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "expected public input of length {} for proof {}, got {}",
+                expected, index, actual
+            ),
+            Self::InvalidSLength {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "expected s of length {} for proof {}, got {}",
+                expected, index, actual
+            ),
+            Self::InvalidAccumulator { index, error } => {
+                write!(f, "prior accumulator {}: {}", index, error)
+            }
+            Self::Hadamard(err) => write!(f, "hadamard accumulation: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for R1CSAccProvingError {}
+
+impl From<HadamardAccProvingError> for R1CSAccProvingError {
+    fn from(err: HadamardAccProvingError) -> Self {
+        Self::Hadamard(err)
+    }
+}
+
+/// The part of a NARK proof the accumulation verifier sees: the public input and
+/// the prover's first message.
+#[derive(Debug, Clone)]
+pub struct R1CSNARKInstance<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub public_input: Vec<C::ScalarExt>,
+    pub pi_1: Pi1<C>,
+}
+
+#[derive(Debug, Clone)]
 pub struct R1CSAccInstance<C>
 where
     C: CurveAffineExt,
@@ -18,6 +102,9 @@ where
     C::Base: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
 {
+    /// The folded constant wire, sum beta^i over the accumulated statements,
+    /// which takes the place of 1 in z = (u, x, s).
+    pub u: C::ScalarExt,
     pub C_x: C,
     pub C_A: C,
     pub C_B: C,
@@ -25,7 +112,7 @@ where
     pub acc_HP_x: HadamardInstance<C>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct R1CSAccWitness<C>
 where
     C: CurveAffineExt,
@@ -41,21 +128,20 @@ where
     pub acc_HP_w: HadamardWitness<C>,
 }
 
-pub struct R1CSAccProver<C>
+pub struct R1CSAccProver<C, T = PoseidonTranscript<C>>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
 {
-    r1cs: R1CS<C>,
-    gens: MultiCommitGens<C>,
-    acc_transcript: PoseidonTranscript<C>,
-    r1cs_transcript: PoseidonTranscript<C>,
+    pk: ProvingKey<C>,
+    acc_transcript: T,
+    r1cs_transcript: T,
 }
 
-#[derive(Debug)]
-pub struct R1CSAccumulator<C>(R1CSAccInstance<C>, R1CSAccWitness<C>)
+#[derive(Debug, Clone)]
+pub struct R1CSAccumulator<C>(pub(crate) R1CSAccInstance<C>, pub(crate) R1CSAccWitness<C>)
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
@@ -63,36 +149,121 @@ where
     C::ScalarExt: DefaultIsZeroes,
     C::Base: PrimeField<Repr = [u8; 32]>;
 
-impl<C> R1CSAccProver<C>
+impl<C> R1CSNARKInstance<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub fn new(public_input: Vec<C::ScalarExt>, pi_1: Pi1<C>) -> Self {
+        Self { public_input, pi_1 }
+    }
+
+    /// Take the instance part of a NARK proof, dropping its second message.
+    pub fn from_proof(proof: &R1CSNARKProof<C>) -> Self {
+        Self {
+            public_input: proof.public_input.clone(),
+            pi_1: proof.pi_1.clone(),
+        }
+    }
+}
+
+impl<C> From<&R1CSNARKProof<C>> for R1CSNARKInstance<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    fn from(proof: &R1CSNARKProof<C>) -> Self {
+        Self::from_proof(proof)
+    }
+}
+
+impl<C> R1CSAccumulator<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+{
+    pub fn new(instance: R1CSAccInstance<C>, witness: R1CSAccWitness<C>) -> Self {
+        Self(instance, witness)
+    }
+
+    /// The part of the accumulator the accumulation verifier checks.
+    pub fn instance(&self) -> &R1CSAccInstance<C> {
+        &self.0
+    }
+
+    /// The part of the accumulator only the prover and the decider see.
+    pub fn witness(&self) -> &R1CSAccWitness<C> {
+        &self.1
+    }
+
+    pub fn into_parts(self) -> (R1CSAccInstance<C>, R1CSAccWitness<C>) {
+        (self.0, self.1)
+    }
+}
+
+impl<C, T> R1CSAccProver<C, T>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
     C::Base: PrimeField<Repr = [u8; 32]>,
+    T: Transcript<C>,
 {
-    pub fn new(
-        r1cs: R1CS<C>,
-        gens: MultiCommitGens<C>,
-        acc_transcript: PoseidonTranscript<C>,
-        r1cs_transcript: PoseidonTranscript<C>,
-    ) -> Self {
+    pub fn new(pk: ProvingKey<C>, acc_transcript: T, r1cs_transcript: T) -> Self {
         Self {
-            r1cs,
-            gens,
+            pk,
             r1cs_transcript,
             acc_transcript,
         }
     }
 
+    /// Accumulate `nizk_proofs` together with the `prior_accs` of earlier steps.
+    /// Either may be empty, but not both.
     pub fn prove_acc(
-        &mut self,
+        &self,
+        prior_accs: &[R1CSAccumulator<C>],
         nizk_proofs: &[R1CSNARKProof<C>],
-    ) -> (R1CSAccumulator<C>, HadamardAccProof<C>) {
+    ) -> Result<(R1CSAccumulator<C>, HadamardAccProof<C>), R1CSAccProvingError> {
+        let m = prior_accs.len();
         let n = nizk_proofs.len();
-        let num_vars = self.r1cs.num_vars;
-        let num_input = self.r1cs.num_input;
+        let shape = self.pk.shape();
+        let num_vars = shape.num_vars;
+        let num_input = shape.num_input;
 
+        if m + n == 0 {
+            return Err(R1CSAccProvingError::NoInstances);
+        }
+
+        for (index, acc) in prior_accs.iter().enumerate() {
+            check_witness_lengths(&acc.1, shape)
+                .map_err(|error| R1CSAccProvingError::InvalidAccumulator { index, error })?;
+        }
+        for (index, proof) in nizk_proofs.iter().enumerate() {
+            if proof.public_input.len() != num_input {
+                return Err(R1CSAccProvingError::InvalidPublicInputLength {
+                    index,
+                    expected: num_input,
+                    actual: proof.public_input.len(),
+                });
+            }
+            if proof.pi_2.s.len() != num_vars {
+                return Err(R1CSAccProvingError::InvalidSLength {
+                    index,
+                    expected: num_vars,
+                    actual: proof.pi_2.s.len(),
+                });
+            }
+        }
+
+        let mut nark_instances = Vec::with_capacity(n);
         let mut hadamard_instances = Vec::with_capacity(n);
         let mut hadamard_witnesses = Vec::with_capacity(n);
         let mut gammas = Vec::with_capacity(n);
@@ -103,22 +274,18 @@ where
             let pi_2 = &r1cs_nizk_proof.pi_2;
             let public_input = &r1cs_nizk_proof.public_input;
 
-            // TODO: Is this correct?
-            self.r1cs_transcript
-                .append_points(&[pi_1.C_A, pi_1.C_B, pi_1.C_C]);
+            let nark_instance = R1CSNARKInstance::from_proof(r1cs_nizk_proof);
 
-            let gamma: C::ScalarExt = self.r1cs_transcript.squeeze(1)[0];
-            gammas.push(gamma.clone());
+            let gamma = compute_gamma(&self.r1cs_transcript, self.pk.digest(), &nark_instance);
+            gammas.push(gamma);
 
             hadamard_instances.push(pi_1_to_hadamard_instance(&gamma, pi_1));
-            self.r1cs_transcript.reset();
+            nark_instances.push(nark_instance);
 
-            let mut s_with_pub_inputs = Vec::with_capacity(num_vars + num_input);
-            s_with_pub_inputs.extend_from_slice(&public_input);
-            s_with_pub_inputs.extend_from_slice(&pi_2.s);
+            let s_with_pub_inputs = z_vector::<C>(C::ScalarExt::one(), public_input, &pi_2.s);
 
-            let a_vec = self.r1cs.A.mul_vector(num_vars, &s_with_pub_inputs);
-            let b_vec = self.r1cs.B.mul_vector(num_vars, &s_with_pub_inputs);
+            let a_vec = shape.A.mul_vector(&s_with_pub_inputs);
+            let b_vec = shape.B.mul_vector(&s_with_pub_inputs);
             let w1 = pi_2.sigma_A;
             let w2 = pi_2.sigma_B;
             let w3 = pi_2.sigma_O;
@@ -132,60 +299,110 @@ where
             });
         }
 
-        let hadamard_acc_prover_transcript =
-            PoseidonTranscript::new(b"hadamard_prover", SpongeCurve::K256);
-        let mut hadamard_acc_prover =
-            HadamardAccProver::new(self.gens.clone(), hadamard_acc_prover_transcript);
+        // The Hadamard accumulators of the prior steps are accumulated along with
+        // the Hadamard instances of the new proofs.
 
-        let (hadamard_acc, hadamard_acc_proof) =
-            hadamard_acc_prover.prove_acc(&hadamard_instances, &hadamard_witnesses);
+        let prior_hadamard_accs = prior_accs
+            .iter()
+            .map(|acc| HadamardAccumulator {
+                qx: acc.0.acc_HP_x.clone(),
+                qw: acc.1.acc_HP_w.clone(),
+            })
+            .collect::<Vec<HadamardAccumulator<C>>>();
 
-        // Step 5
+        let prior_acc_instances = prior_accs
+            .iter()
+            .map(|acc| acc.0.clone())
+            .collect::<Vec<R1CSAccInstance<C>>>();
+        let nark_C_xs = commit_public_inputs(self.pk.gens(), &nark_instances);
 
-        for hadamard_inst in hadamard_instances {
-            self.acc_transcript.append_point(&hadamard_inst.0);
-            self.acc_transcript.append_point(&hadamard_inst.1);
-            self.acc_transcript.append_point(&hadamard_inst.2);
-        }
+        // This step's challenges are derived on its own fork of the base transcript
+        let mut transcript = self.acc_transcript.fork(b"r1cs-acc-step");
+        append_acc_statement(
+            &mut transcript,
+            &prior_acc_instances,
+            &nark_instances,
+            &nark_C_xs,
+            &hadamard_instances,
+        );
+
+        let mut hadamard_acc_prover = HadamardAccProver::new(
+            self.pk.gens().prefix(shape.num_cons),
+            transcript.fork(b"hadamard"),
+        );
+
+        let (hadamard_acc, hadamard_acc_proof) = hadamard_acc_prover.prove_acc(
+            &prior_hadamard_accs,
+            &hadamard_instances,
+            &hadamard_witnesses,
+        )?;
+
+        // Step 5
 
-        let beta: C::ScalarExt = self.acc_transcript.squeeze(1)[0];
+        let beta = compute_beta(&mut transcript, &hadamard_acc.qx);
 
         // Step 6
 
-        let mut C_x: C::Curve = C::identity().into();
-        let mut C_A: C::Curve = C::identity().into();
-        let mut C_B: C::Curve = C::identity().into();
-        let mut C_C: C::Curve = C::identity().into();
+        let (C_x, C_A, C_B, C_C) = fold_commitments(
+            &prior_acc_instances,
+            &nark_instances,
+            &nark_C_xs,
+            &gammas,
+            &beta,
+        );
 
-        let beta_pows = (0..n)
+        let beta_pows = (0..(m + n))
             .map(|i| beta.pow(&[i as u64, 0, 0, 0]))
             .collect::<Vec<C::ScalarExt>>();
 
-        for (i, proof) in nizk_proofs.iter().enumerate() {
-            C_x += self.gens.commit(&proof.public_input, &C::ScalarExt::zero()) * beta_pows[i];
-            C_A += proof.pi_1.C_A + (proof.pi_1.C_A_prime * gammas[i]).into();
-            C_B += proof.pi_1.C_B + (proof.pi_1.C_B_prime * gammas[i]).into();
-            C_C += proof.pi_1.C_C + (proof.pi_1.C_C_prime * gammas[i]).into();
-        }
-
         // Step 7
 
-        let mut x = vec![C::ScalarExt::zero(); num_input];
-        let mut s = vec![C::ScalarExt::zero(); num_vars];
+        // Fold each coordinate of x and s over the prior accumulators, then the new proofs
+        let x = par_map(num_input, |k| {
+            let from_accs = prior_accs
+                .iter()
+                .zip(beta_pows.iter())
+                .fold(C::ScalarExt::zero(), |sum, (acc, beta_pow)| {
+                    sum + acc.1.x[k] * beta_pow
+                });
+            nizk_proofs
+                .iter()
+                .zip(beta_pows[m..].iter())
+                .fold(from_accs, |sum, (proof, beta_pow)| {
+                    sum + proof.public_input[k] * beta_pow
+                })
+        });
+        let s = par_map(num_vars, |k| {
+            let from_accs = prior_accs
+                .iter()
+                .zip(beta_pows.iter())
+                .fold(C::ScalarExt::zero(), |sum, (acc, beta_pow)| {
+                    sum + acc.1.s[k] * beta_pow
+                });
+            nizk_proofs
+                .iter()
+                .zip(beta_pows[m..].iter())
+                .fold(from_accs, |sum, (proof, beta_pow)| {
+                    sum + proof.pi_2.s[k] * beta_pow
+                })
+        });
+
         let mut sigma_A = C::ScalarExt::zero();
         let mut sigma_B = C::ScalarExt::zero();
         let mut sigma_C = C::ScalarExt::zero();
 
+        for (i, acc) in prior_accs.iter().enumerate() {
+            let acc_w = &acc.1;
+            sigma_A += acc_w.sigma_A * beta_pows[i];
+            sigma_B += acc_w.sigma_B * beta_pows[i];
+            sigma_C += acc_w.sigma_C * beta_pows[i];
+        }
+
         for (i, proof) in nizk_proofs.iter().enumerate() {
-            for k in 0..num_input {
-                x[k] += proof.public_input[k] * beta_pows[i];
-            }
-            for k in 0..num_vars {
-                s[k] += proof.pi_2.s[k] * beta_pows[i];
-            }
-            sigma_A += proof.pi_2.sigma_A * beta_pows[i];
-            sigma_B += proof.pi_2.sigma_B * beta_pows[i];
-            sigma_C += proof.pi_2.sigma_C * beta_pows[i];
+            let beta_pow = beta_pows[m + i];
+            sigma_A += proof.pi_2.sigma_A * beta_pow;
+            sigma_B += proof.pi_2.sigma_B * beta_pow;
+            sigma_C += proof.pi_2.sigma_C * beta_pow;
         }
 
         let acc_witness = R1CSAccWitness {
@@ -197,16 +414,19 @@ where
             acc_HP_w: hadamard_acc.qw,
         };
 
+        let u = fold_constant(&prior_acc_instances, n, &beta);
+
         let acc_instance = R1CSAccInstance::<C> {
-            C_x: C_x.into(),
-            C_A: C_A.into(),
-            C_B: C_B.into(),
-            C_C: C_C.into(),
+            u,
+            C_x,
+            C_A,
+            C_B,
+            C_C,
             acc_HP_x: hadamard_acc.qx,
         };
 
         let acc = R1CSAccumulator::<C>(acc_instance, acc_witness);
 
-        (acc, hadamard_acc_proof)
+        Ok((acc, hadamard_acc_proof))
     }
 }
@@ -1,18 +1,73 @@
-use crate::accumulation::hadamard::acc_verifier::HadamardAccVerifier;
-use crate::accumulation::hadamard::{
-    HadamardAccProof, HadamardAccumulator, HadamardInstance, HadamardWitness,
+use crate::accumulation::hadamard::acc_verifier::{
+    HadamardAccVerificationError, HadamardAccVerifier,
 };
-use crate::{CurveAffineExt, MultiCommitGens};
-use ff::{Field, PrimeField, PrimeFieldBits};
-use halo2curves::FieldExt;
-use poseidon_transcript::sponge::SpongeCurve;
+use crate::accumulation::hadamard::{HadamardAccProof, HadamardInstance};
+use crate::r1cs::VerifyingKey;
+use crate::{CurveAffineExt, Transcript};
+use ff::{PrimeField, PrimeFieldBits};
 use poseidon_transcript::transcript::PoseidonTranscript;
+use std::fmt;
 use zeroize::DefaultIsZeroes;
 
-use super::acc_prover::{R1CSAccInstance, R1CSAccumulator};
-use super::utils::pi_1_to_hadamard_instance;
+use super::acc_prover::{R1CSAccInstance, R1CSNARKInstance};
+use super::utils::{
+    append_acc_statement, commit_public_inputs, compute_beta, compute_gamma, fold_commitments,
+    fold_constant, pi_1_to_hadamard_instance,
+};
+
+/// The check of the R1CS accumulation verifier that an accumulation step failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum R1CSAccVerificationError {
+    /// No instances were given to accumulate.
+    NoInstances,
+    /// The public input of the new instance at `index` does not have `num_input` entries.
+    InvalidPublicInputLength {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// The Hadamard accumulation verifier rejected the Hadamard instances.
+    Hadamard(HadamardAccVerificationError),
+    /// The accumulator's constant wire is not the folded constant.
+    UMismatch,
+    CxMismatch,
+    CAMismatch,
+    CBMismatch,
+    CCMismatch,
+}
+
+impl fmt::Display for R1CSAccVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoInstances => write!(f, "no instances to accumulate"),
+            Self::InvalidPublicInputLength {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "expected public input of length {} for instance {}, got {}",
+                expected, index, actual
+            ),
+            Self::Hadamard(err) => write!(f, "hadamard accumulation: {}", err),
+            Self::UMismatch => write!(f, "folded u does not match the accumulator"),
+            Self::CxMismatch => write!(f, "folded C_x does not match the accumulator"),
+            Self::CAMismatch => write!(f, "folded C_A does not match the accumulator"),
+            Self::CBMismatch => write!(f, "folded C_B does not match the accumulator"),
+            Self::CCMismatch => write!(f, "folded C_C does not match the accumulator"),
+        }
+    }
+}
 
-pub struct R1CSAccVerifier<C>
+impl std::error::Error for R1CSAccVerificationError {}
+
+impl From<HadamardAccVerificationError> for R1CSAccVerificationError {
+    fn from(err: HadamardAccVerificationError) -> Self {
+        Self::Hadamard(err)
+    }
+}
+
+pub struct R1CSAccVerifier<C, T = PoseidonTranscript<C>>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
@@ -20,57 +75,120 @@ where
     C::Base: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
 {
-    acc_transcript: PoseidonTranscript<C>,
-    r1cs_transcript: PoseidonTranscript<C>,
+    vk: VerifyingKey<C>,
+    acc_transcript: T,
+    r1cs_transcript: T,
 }
 
-impl<C> R1CSAccVerifier<C>
+impl<C, T> R1CSAccVerifier<C, T>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::Base: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
 {
-    pub fn new(
-        acc_transcript: PoseidonTranscript<C>,
-        r1cs_transcript: PoseidonTranscript<C>,
-    ) -> Self {
+    pub fn new(vk: VerifyingKey<C>, acc_transcript: T, r1cs_transcript: T) -> Self {
         Self {
+            vk,
             acc_transcript,
             r1cs_transcript,
         }
     }
 
+    /// Check that `acc_instance` accumulates the `prior_acc_instances` of earlier steps
+    /// together with `nark_instances`.
     pub fn verify(
-        &mut self,
-        acc: &R1CSAccumulator<C>,
-        accumulated_instances: &[R1CSAccInstance<C>],
+        &self,
+        acc_instance: &R1CSAccInstance<C>,
+        prior_acc_instances: &[R1CSAccInstance<C>],
+        nark_instances: &[R1CSNARKInstance<C>],
         proof: &HadamardAccProof<C>,
-    ) {
-        let hadamard_acc_verifier = HadamardAccVerifier::new(self.acc_transcript.clone());
-        let n = accumulated_instances.len();
+    ) -> Result<(), R1CSAccVerificationError> {
+        let n = nark_instances.len();
+        if prior_acc_instances.len() + n == 0 {
+            return Err(R1CSAccVerificationError::NoInstances);
+        }
+
+        let num_input = self.vk.shape().num_input;
+        for (index, nark_instance) in nark_instances.iter().enumerate() {
+            if nark_instance.public_input.len() != num_input {
+                return Err(R1CSAccVerificationError::InvalidPublicInputLength {
+                    index,
+                    expected: num_input,
+                    actual: nark_instance.public_input.len(),
+                });
+            }
+        }
 
         let mut gammas = Vec::with_capacity(n);
+        let mut hadamard_instances = Vec::with_capacity(n);
 
-        //        let mut hadamard_instances = Vec::with_capacity(n);
-        //        let mut hadamard_witnesses = Vec::with_capacity(n);
+        for nark_instance in nark_instances {
+            let gamma = compute_gamma(&self.r1cs_transcript, self.vk.digest(), nark_instance);
+            gammas.push(gamma);
 
-        for i in 0..n {
-            let acc_instance = &accumulated_instances[i];
+            hadamard_instances.push(pi_1_to_hadamard_instance(&gamma, &nark_instance.pi_1));
+        }
+
+        let nark_C_xs = commit_public_inputs(self.vk.gens(), nark_instances);
+
+        // Replay the step's own fork of the base transcript, as the prover derived it
+        let mut transcript = self.acc_transcript.fork(b"r1cs-acc-step");
+        append_acc_statement(
+            &mut transcript,
+            prior_acc_instances,
+            nark_instances,
+            &nark_C_xs,
+            &hadamard_instances,
+        );
+
+        // Check the Hadamard accumulation step
 
-            // TODO: Is this correct?
-            self.r1cs_transcript.append_points(&[
-                acc_instance.C_A,
-                acc_instance.C_B,
-                acc_instance.C_C,
-            ]);
+        let hadamard_acc_verifier = HadamardAccVerifier::new(transcript.fork(b"hadamard"));
+        let prior_hadamard_instances = prior_acc_instances
+            .iter()
+            .map(|acc_inst| acc_inst.acc_HP_x.clone())
+            .collect::<Vec<HadamardInstance<C>>>();
+        hadamard_acc_verifier.verify(
+            &acc_instance.acc_HP_x,
+            &prior_hadamard_instances,
+            &hadamard_instances,
+            proof,
+        )?;
 
-            let gamma: C::ScalarExt = self.r1cs_transcript.squeeze(1)[0];
-            gammas.push(gamma.clone());
+        // Recompute beta
 
-            self.r1cs_transcript.reset();
+        let beta = compute_beta(&mut transcript, &acc_instance.acc_HP_x);
+
+        // Check the folded constant and commitments
+
+        if fold_constant(prior_acc_instances, n, &beta) != acc_instance.u {
+            return Err(R1CSAccVerificationError::UMismatch);
         }
-        // TBD
+
+        let (C_x, C_A, C_B, C_C) = fold_commitments(
+            prior_acc_instances,
+            nark_instances,
+            &nark_C_xs,
+            &gammas,
+            &beta,
+        );
+
+        if C_x != acc_instance.C_x {
+            return Err(R1CSAccVerificationError::CxMismatch);
+        }
+        if C_A != acc_instance.C_A {
+            return Err(R1CSAccVerificationError::CAMismatch);
+        }
+        if C_B != acc_instance.C_B {
+            return Err(R1CSAccVerificationError::CBMismatch);
+        }
+        if C_C != acc_instance.C_C {
+            return Err(R1CSAccVerificationError::CCMismatch);
+        }
+
+        Ok(())
     }
 }
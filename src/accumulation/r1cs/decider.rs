@@ -0,0 +1,153 @@
+use crate::accumulation::hadamard::decider::{HadamardDecider, HadamardDecisionError};
+use crate::r1cs::{z_vector, R1CSShape, VerifyingKey};
+use crate::CurveAffineExt;
+use ff::{Field, PrimeField, PrimeFieldBits};
+use std::fmt;
+use zeroize::DefaultIsZeroes;
+
+use super::acc_prover::{R1CSAccWitness, R1CSAccumulator};
+
+/// The check of the R1CS decider that an accumulator failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum R1CSAccDecisionError {
+    InvalidXLength {
+        expected: usize,
+        actual: usize,
+    },
+    InvalidSLength {
+        expected: usize,
+        actual: usize,
+    },
+    /// a or b of the Hadamard witness does not have one entry per constraint.
+    InvalidHadamardWitnessLength {
+        expected: usize,
+        actual: usize,
+    },
+    /// cm(x) != C_x
+    CxMismatch,
+    /// cm(A(x, s); sigma_A) != C_A
+    CAMismatch,
+    /// cm(B(x, s); sigma_B) != C_B
+    CBMismatch,
+    /// cm(C(x, s); sigma_C) != C_C
+    CCMismatch,
+    /// The Hadamard decider rejected the Hadamard accumulator.
+    Hadamard(HadamardDecisionError),
+}
+
+impl fmt::Display for R1CSAccDecisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidXLength { expected, actual } => {
+                write!(f, "expected x of length {}, got {}", expected, actual)
+            }
+            Self::InvalidSLength { expected, actual } => {
+                write!(f, "expected s of length {}, got {}", expected, actual)
+            }
+            Self::InvalidHadamardWitnessLength { expected, actual } => {
+                write!(
+                    f,
+                    "expected hadamard witness vectors of length {}, got {}",
+                    expected, actual
+                )
+            }
+            Self::CxMismatch => write!(f, "commitment to x does not match C_x"),
+            Self::CAMismatch => write!(f, "commitment to A(x, s) does not match C_A"),
+            Self::CBMismatch => write!(f, "commitment to B(x, s) does not match C_B"),
+            Self::CCMismatch => write!(f, "commitment to C(x, s) does not match C_C"),
+            Self::Hadamard(err) => write!(f, "hadamard accumulator: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for R1CSAccDecisionError {}
+
+impl From<HadamardDecisionError> for R1CSAccDecisionError {
+    fn from(err: HadamardDecisionError) -> Self {
+        Self::Hadamard(err)
+    }
+}
+
+/// Decide whether an R1CS accumulator is valid,
+/// which holds if and only if all the NARK proofs accumulated into it are.
+pub fn decide<C>(acc: &R1CSAccumulator<C>, vk: &VerifyingKey<C>) -> Result<(), R1CSAccDecisionError>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    let acc_x = &acc.0;
+    let acc_w = &acc.1;
+
+    let shape = vk.shape();
+    let gens = vk.gens();
+
+    check_witness_lengths(acc_w, shape)?;
+
+    // Check that the folded public input opens C_x
+
+    if gens.commit_prefix(&acc_w.x, &C::ScalarExt::zero()) != acc_x.C_x {
+        return Err(R1CSAccDecisionError::CxMismatch);
+    }
+
+    // Check that the folded A(u, x, s), B(u, x, s), C(u, x, s) open C_A, C_B, C_C
+
+    let s_with_pub_input = z_vector::<C>(acc_x.u, &acc_w.x, &acc_w.s);
+
+    let (s_A, s_B, s_C) = shape.multiply_vec(&s_with_pub_input);
+
+    if gens.commit_prefix(&s_A, &acc_w.sigma_A) != acc_x.C_A {
+        return Err(R1CSAccDecisionError::CAMismatch);
+    }
+    if gens.commit_prefix(&s_B, &acc_w.sigma_B) != acc_x.C_B {
+        return Err(R1CSAccDecisionError::CBMismatch);
+    }
+    if gens.commit_prefix(&s_C, &acc_w.sigma_C) != acc_x.C_C {
+        return Err(R1CSAccDecisionError::CCMismatch);
+    }
+
+    // Check the Hadamard accumulator
+
+    HadamardDecider::new(gens.prefix(shape.num_cons))
+        .decide_parts(&acc_x.acc_HP_x, &acc_w.acc_HP_w)?;
+
+    Ok(())
+}
+
+// The lengths of the witness vectors of an accumulator for `shape`, which the accumulation
+// prover also checks for the prior accumulators it is given.
+pub(crate) fn check_witness_lengths<C>(
+    acc_w: &R1CSAccWitness<C>,
+    shape: &R1CSShape<C>,
+) -> Result<(), R1CSAccDecisionError>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    if acc_w.x.len() != shape.num_input {
+        return Err(R1CSAccDecisionError::InvalidXLength {
+            expected: shape.num_input,
+            actual: acc_w.x.len(),
+        });
+    }
+    if acc_w.s.len() != shape.num_vars {
+        return Err(R1CSAccDecisionError::InvalidSLength {
+            expected: shape.num_vars,
+            actual: acc_w.s.len(),
+        });
+    }
+    for vec in [&acc_w.acc_HP_w.a_vec, &acc_w.acc_HP_w.b_vec] {
+        if vec.len() != shape.num_cons {
+            return Err(R1CSAccDecisionError::InvalidHadamardWitnessLength {
+                expected: shape.num_cons,
+                actual: vec.len(),
+            });
+        }
+    }
+
+    Ok(())
+}
@@ -1,15 +1,25 @@
 mod acc_prover;
 mod acc_verifier;
+mod decider;
 mod utils;
 
-pub use acc_prover::R1CSAccProver;
+pub use acc_prover::{
+    R1CSAccInstance, R1CSAccProver, R1CSAccProvingError, R1CSAccWitness, R1CSAccumulator,
+    R1CSNARKInstance,
+};
+pub use acc_verifier::{R1CSAccVerificationError, R1CSAccVerifier};
+pub use decider::{decide, R1CSAccDecisionError};
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        r1cs::{R1CSNARKProver, R1CS},
-        MultiCommitGens,
+        accumulation::hadamard::{
+            acc_verifier::HadamardAccVerificationError, decider::HadamardDecisionError,
+        },
+        r1cs::{setup, z_vector, R1CSNARKProver, R1CSShape, Verifier},
+        transcript::KeccakTranscript,
     };
+    use ff::Field;
     use halo2curves::secq256k1::Secq256k1Affine;
     use poseidon_transcript::{sponge::SpongeCurve, transcript::PoseidonTranscript};
 
@@ -22,23 +32,21 @@ mod tests {
         let num_input = 10;
         type C = Secq256k1Affine;
 
-        let gens = MultiCommitGens::new(num_vars, b"test_r1cs_accumulation");
+        let (shape, instance, witness) =
+            R1CSShape::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let (pk, vk) = setup(&shape, b"test_r1cs_accumulation");
 
-        let r1cs = R1CS::produce_synthetic_r1cs(num_cons, num_vars, num_input);
-
-        let r1cs_nizk_prover_transcript =
-            PoseidonTranscript::new(b"test_r1cs_accumulation", SpongeCurve::K256);
-        let mut r1cs_nizk_prover =
-            R1CSNARKProver::new(r1cs.clone(), r1cs_nizk_prover_transcript, gens.clone());
-
-        // Generate the R1CS NARK proofs
+        // Generate the R1CS NARK proofs.
+        // Each proof starts from the base transcript, which the accumulation prover replays.
 
         let n = 3; // Number of r1cs instances to accumulate
 
-        let mut r1cs_nizk_proofs = Vec::with_capacity(n);
-        for _ in 0..n {
-            r1cs_nizk_proofs.push(r1cs_nizk_prover.prove(&r1cs.witness, &r1cs.public_input));
-        }
+        let r1cs_nizk_prover_transcript =
+            PoseidonTranscript::new(b"test_r1cs_accumulation", SpongeCurve::K256);
+        let mut r1cs_nizk_prover = R1CSNARKProver::new(pk.clone(), r1cs_nizk_prover_transcript);
+        let r1cs_nizk_proofs = (0..n)
+            .map(|_| r1cs_nizk_prover.prove(&instance, &witness).unwrap())
+            .collect::<Vec<_>>();
 
         // Generate the accumulation proof
 
@@ -48,15 +56,490 @@ mod tests {
         let r1cs_nizk_prover_transcript =
             PoseidonTranscript::new(b"test_r1cs_accumulation", SpongeCurve::K256);
 
-        let mut r1cs_acc_prover = R1CSAccProver::<C>::new(
-            r1cs,
-            gens.clone(),
+        let r1cs_acc_prover = R1CSAccProver::<C>::new(
+            pk.clone(),
             acc_prover_transcript,
             r1cs_nizk_prover_transcript,
         );
 
-        let (accumulator, acc_proof) = r1cs_acc_prover.prove_acc(&r1cs_nizk_proofs);
-        println!("Accumulator: {:?}", accumulator);
-        println!("Accumulation proof: {:?}", acc_proof);
+        let (mut accumulator, acc_proof) =
+            r1cs_acc_prover.prove_acc(&[], &r1cs_nizk_proofs).unwrap();
+
+        // Verify the accumulation step
+
+        let nark_instances = r1cs_nizk_proofs
+            .iter()
+            .map(R1CSNARKInstance::from_proof)
+            .collect::<Vec<R1CSNARKInstance<C>>>();
+
+        let new_acc_verifier = || {
+            R1CSAccVerifier::<C>::new(
+                vk.clone(),
+                PoseidonTranscript::new(b"test_r1cs_accumulation", SpongeCurve::K256),
+                PoseidonTranscript::new(b"test_r1cs_accumulation", SpongeCurve::K256),
+            )
+        };
+
+        assert_eq!(
+            new_acc_verifier().verify(accumulator.instance(), &[], &nark_instances, &acc_proof),
+            Ok(())
+        );
+
+        assert_eq!(decide(&accumulator, &vk), Ok(()));
+
+        // The folded constant is 1 + beta + beta^2, not 1
+        let mut wrong_u = accumulator.clone();
+        wrong_u.0.u = <C as halo2curves::CurveAffine>::ScalarExt::one();
+        assert_eq!(
+            new_acc_verifier().verify(wrong_u.instance(), &[], &nark_instances, &acc_proof),
+            Err(R1CSAccVerificationError::UMismatch)
+        );
+        assert_eq!(decide(&wrong_u, &vk), Err(R1CSAccDecisionError::CAMismatch));
+
+        assert_eq!(
+            new_acc_verifier().verify(
+                accumulator.instance(),
+                &[],
+                &nark_instances[1..],
+                &acc_proof
+            ),
+            Err(R1CSAccVerificationError::Hadamard(
+                HadamardAccVerificationError::InvalidProofLength {
+                    expected: 2 * (n - 2),
+                    actual: 2 * (n - 1),
+                }
+            ))
+        );
+
+        // A shorter public input would commit to the same C_x as the zero-padded one
+        let mut short_input = nark_instances.clone();
+        short_input[1].public_input.pop();
+        assert_eq!(
+            new_acc_verifier().verify(accumulator.instance(), &[], &short_input, &acc_proof),
+            Err(R1CSAccVerificationError::InvalidPublicInputLength {
+                index: 1,
+                expected: num_input,
+                actual: num_input - 1,
+            })
+        );
+
+        let mut long_input = nark_instances.clone();
+        long_input[2]
+            .public_input
+            .push(<C as halo2curves::CurveAffine>::ScalarExt::zero());
+        assert_eq!(
+            new_acc_verifier().verify(accumulator.instance(), &[], &long_input, &acc_proof),
+            Err(R1CSAccVerificationError::InvalidPublicInputLength {
+                index: 2,
+                expected: num_input,
+                actual: num_input + 1,
+            })
+        );
+
+        accumulator.0.C_x = accumulator.0.C_A;
+        assert_eq!(
+            new_acc_verifier().verify(accumulator.instance(), &[], &nark_instances, &acc_proof),
+            Err(R1CSAccVerificationError::CxMismatch)
+        );
+
+        accumulator.0.acc_HP_x.2 = accumulator.0.C_A;
+        assert_eq!(
+            new_acc_verifier().verify(accumulator.instance(), &[], &nark_instances, &acc_proof),
+            Err(R1CSAccVerificationError::Hadamard(
+                HadamardAccVerificationError::C3Mismatch
+            ))
+        );
+    }
+
+    #[test]
+    fn test_r1cs_accumulation_gamma_matches_verifier() {
+        type C = Secq256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(8, 8, 2);
+        let (pk, vk) = setup(&shape, b"test_r1cs_gamma");
+        let new_transcript = || PoseidonTranscript::new(b"test_r1cs_gamma", SpongeCurve::K256);
+
+        let proofs = (0..3)
+            .map(|_| {
+                R1CSNARKProver::new(pk.clone(), new_transcript())
+                    .prove(&instance, &witness)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        // The NARK verifier accepts a proof only if cm(A(x, s); sigma_A) = C_A + gamma * C_A'
+        // for the gamma it derives, and that equation has no other solution in gamma.
+        // So the gamma of the accumulation prover and verifier is the NARK verifier's
+        // if it satisfies the equation too.
+        let base_transcript = new_transcript();
+        let verifier = Verifier::new(vk.clone(), new_transcript());
+        for proof in &proofs {
+            assert_eq!(verifier.verify(proof, &instance), Ok(()));
+
+            let z = z_vector::<C>(F::one(), &proof.public_input, &proof.pi_2.s);
+            let (s_A, _, _) = shape.multiply_vec(&z);
+            let comm_s_A = vk.gens().commit_prefix(&s_A, &proof.pi_2.sigma_A);
+            let opens_C_A = |gamma: F| {
+                let C_A: C = (proof.pi_1.C_A_prime * gamma + proof.pi_1.C_A).into();
+                C_A == comm_s_A
+            };
+
+            let nark_instance = R1CSNARKInstance::from_proof(proof);
+            let gamma = utils::compute_gamma(&base_transcript, vk.digest(), &nark_instance);
+            assert!(opens_C_A(gamma));
+            assert!(!opens_C_A(gamma + F::one()));
+        }
+    }
+
+    #[test]
+    fn test_r1cs_accumulation_rejects_changed_nark_instance() {
+        type C = Secq256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(8, 8, 2);
+        let (pk, vk) = setup(&shape, b"test_r1cs_changed_instance");
+        let new_transcript =
+            || PoseidonTranscript::new(b"test_r1cs_changed_instance", SpongeCurve::K256);
+
+        let proofs = (0..2)
+            .map(|_| {
+                R1CSNARKProver::new(pk.clone(), new_transcript())
+                    .prove(&instance, &witness)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let nark_instances = proofs
+            .iter()
+            .map(R1CSNARKInstance::from_proof)
+            .collect::<Vec<_>>();
+
+        let acc_prover = R1CSAccProver::<C>::new(pk, new_transcript(), new_transcript());
+        let (accumulator, acc_proof) = acc_prover.prove_acc(&[], &proofs).unwrap();
+
+        let acc_verifier = R1CSAccVerifier::<C>::new(vk, new_transcript(), new_transcript());
+        let verify = |nark_instances: &[R1CSNARKInstance<C>]| {
+            acc_verifier.verify(accumulator.instance(), &[], nark_instances, &acc_proof)
+        };
+        assert_eq!(verify(&nark_instances), Ok(()));
+
+        // The statement of a proof changed after it was proven
+        let mut changed_input = nark_instances.clone();
+        changed_input[1].public_input[0] += F::one();
+        assert!(verify(&changed_input).is_err());
+
+        // Its first message changed after gamma was derived from it
+        let mut changed_pi_1 = nark_instances.clone();
+        changed_pi_1[0].pi_1.C_A_prime = nark_instances[0].pi_1.C_B_prime;
+        assert!(verify(&changed_pi_1).is_err());
+    }
+
+    #[test]
+    fn test_r1cs_decider_rejects_invalid_accumulator() {
+        let num_cons = 8;
+        let num_vars = 8;
+        let num_input = 2;
+        type C = Secq256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        let (shape, instance, witness) =
+            R1CSShape::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let (pk, vk) = setup(&shape, b"test_r1cs_decider");
+
+        let r1cs_nizk_proofs = (0..2)
+            .map(|_| {
+                let transcript = PoseidonTranscript::new(b"test_r1cs_decider", SpongeCurve::K256);
+                let mut prover = R1CSNARKProver::new(pk.clone(), transcript);
+                prover.prove(&instance, &witness).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let r1cs_acc_prover = R1CSAccProver::<C>::new(
+            pk.clone(),
+            PoseidonTranscript::new(b"test_r1cs_decider", SpongeCurve::K256),
+            PoseidonTranscript::new(b"test_r1cs_decider", SpongeCurve::K256),
+        );
+
+        let (mut accumulator, _) = r1cs_acc_prover.prove_acc(&[], &r1cs_nizk_proofs).unwrap();
+        assert_eq!(decide(&accumulator, &vk), Ok(()));
+
+        let mut short_a = accumulator.clone();
+        short_a.1.acc_HP_w.a_vec.pop();
+        assert_eq!(
+            decide(&short_a, &vk),
+            Err(R1CSAccDecisionError::InvalidHadamardWitnessLength {
+                expected: num_cons,
+                actual: num_cons - 1,
+            })
+        );
+
+        let mut long_b = accumulator.clone();
+        long_b.1.acc_HP_w.b_vec.push(F::zero());
+        assert_eq!(
+            decide(&long_b, &vk),
+            Err(R1CSAccDecisionError::InvalidHadamardWitnessLength {
+                expected: num_cons,
+                actual: num_cons + 1,
+            })
+        );
+
+        accumulator.1.acc_HP_w.a_vec[0] += F::one();
+        assert_eq!(
+            decide(&accumulator, &vk),
+            Err(R1CSAccDecisionError::Hadamard(
+                HadamardDecisionError::C1Mismatch
+            ))
+        );
+
+        accumulator.1.sigma_C += F::one();
+        assert_eq!(
+            decide(&accumulator, &vk),
+            Err(R1CSAccDecisionError::CCMismatch)
+        );
+
+        accumulator.1.s[0] += F::one();
+        assert_eq!(
+            decide(&accumulator, &vk),
+            Err(R1CSAccDecisionError::CAMismatch)
+        );
+
+        accumulator.1.x[0] += F::one();
+        assert_eq!(
+            decide(&accumulator, &vk),
+            Err(R1CSAccDecisionError::CxMismatch)
+        );
+    }
+
+    #[test]
+    fn test_r1cs_accumulation_with_prior_accumulators() {
+        let num_cons = 8;
+        let num_vars = 8;
+        let num_input = 2;
+        type C = Secq256k1Affine;
+
+        let (shape, instance, witness) =
+            R1CSShape::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let (pk, vk) = setup(&shape, b"test_r1cs_multi_step");
+
+        let prove_nark = || {
+            let transcript = PoseidonTranscript::new(b"test_r1cs_multi_step", SpongeCurve::K256);
+            let mut prover = R1CSNARKProver::new(pk.clone(), transcript);
+            prover.prove(&instance, &witness).unwrap()
+        };
+
+        let acc_prover = R1CSAccProver::<C>::new(
+            pk.clone(),
+            PoseidonTranscript::new(b"test_r1cs_multi_step", SpongeCurve::K256),
+            PoseidonTranscript::new(b"test_r1cs_multi_step", SpongeCurve::K256),
+        );
+        let acc_verifier = R1CSAccVerifier::<C>::new(
+            vk.clone(),
+            PoseidonTranscript::new(b"test_r1cs_multi_step", SpongeCurve::K256),
+            PoseidonTranscript::new(b"test_r1cs_multi_step", SpongeCurve::K256),
+        );
+
+        // There is nothing to accumulate without prior accumulators or proofs
+        assert_eq!(
+            acc_prover.prove_acc(&[], &[]).err(),
+            Some(R1CSAccProvingError::NoInstances)
+        );
+
+        // Malformed proofs are rejected before anything is folded
+        let mut short_input = prove_nark();
+        short_input.public_input.pop();
+        assert_eq!(
+            acc_prover
+                .prove_acc(&[], &[prove_nark(), short_input])
+                .err(),
+            Some(R1CSAccProvingError::InvalidPublicInputLength {
+                index: 1,
+                expected: num_input,
+                actual: num_input - 1,
+            })
+        );
+
+        let mut long_s = prove_nark();
+        long_s
+            .pi_2
+            .s
+            .push(<C as halo2curves::CurveAffine>::ScalarExt::zero());
+        assert_eq!(
+            acc_prover.prove_acc(&[], &[long_s]).err(),
+            Some(R1CSAccProvingError::InvalidSLength {
+                index: 0,
+                expected: num_vars,
+                actual: num_vars + 1,
+            })
+        );
+
+        // Step 1: start an accumulator from two proofs
+
+        let proofs_1 = vec![prove_nark(), prove_nark()];
+        let instances_1 = proofs_1
+            .iter()
+            .map(R1CSNARKInstance::from_proof)
+            .collect::<Vec<_>>();
+        let (acc_1, acc_proof_1) = acc_prover.prove_acc(&[], &proofs_1).unwrap();
+        assert_eq!(
+            acc_verifier.verify(acc_1.instance(), &[], &instances_1, &acc_proof_1),
+            Ok(())
+        );
+
+        // Step 2: the running accumulator absorbs a new proof
+
+        let proofs_2 = vec![prove_nark()];
+        let instances_2 = proofs_2
+            .iter()
+            .map(R1CSNARKInstance::from_proof)
+            .collect::<Vec<_>>();
+        let (acc_2, acc_proof_2) = acc_prover.prove_acc(&[acc_1.clone()], &proofs_2).unwrap();
+        assert_eq!(
+            acc_verifier.verify(
+                acc_2.instance(),
+                &[acc_1.instance().clone()],
+                &instances_2,
+                &acc_proof_2
+            ),
+            Ok(())
+        );
+        assert_eq!(decide(&acc_2, &vk), Ok(()));
+
+        // A verifier that has not seen step 1 checks step 2 on its own,
+        // and still ties it to the prior accumulator it was given
+        let new_acc_verifier = || {
+            R1CSAccVerifier::<C>::new(
+                vk.clone(),
+                PoseidonTranscript::new(b"test_r1cs_multi_step", SpongeCurve::K256),
+                PoseidonTranscript::new(b"test_r1cs_multi_step", SpongeCurve::K256),
+            )
+        };
+        assert_eq!(
+            new_acc_verifier().verify(
+                acc_2.instance(),
+                &[acc_1.instance().clone()],
+                &instances_2,
+                &acc_proof_2
+            ),
+            Ok(())
+        );
+        assert!(new_acc_verifier()
+            .verify(acc_2.instance(), &[], &instances_2, &acc_proof_2)
+            .is_err());
+
+        // A prior accumulator whose witness does not fit the shape
+        let mut short_acc = acc_1.clone();
+        short_acc.1.acc_HP_w.b_vec.pop();
+        assert_eq!(
+            acc_prover.prove_acc(&[acc_1.clone(), short_acc], &[]).err(),
+            Some(R1CSAccProvingError::InvalidAccumulator {
+                index: 1,
+                error: R1CSAccDecisionError::InvalidHadamardWitnessLength {
+                    expected: num_cons,
+                    actual: num_cons - 1,
+                },
+            })
+        );
+
+        // Step 3: merge two accumulators without new proofs
+
+        let (acc_3, acc_proof_3) = acc_prover
+            .prove_acc(&[acc_1.clone(), acc_2.clone()], &[])
+            .unwrap();
+        assert_eq!(
+            acc_verifier.verify(
+                acc_3.instance(),
+                &[acc_1.instance().clone(), acc_2.instance().clone()],
+                &[],
+                &acc_proof_3
+            ),
+            Ok(())
+        );
+        assert_eq!(decide(&acc_3, &vk), Ok(()));
+    }
+
+    #[test]
+    fn test_r1cs_accumulation_keccak_transcript() {
+        type C = Secq256k1Affine;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(8, 8, 2);
+        let (pk, vk) = setup(&shape, b"test_r1cs_keccak");
+
+        let proofs = (0..2)
+            .map(|_| {
+                let transcript = KeccakTranscript::new(b"test_r1cs_keccak");
+                R1CSNARKProver::new(pk.clone(), transcript)
+                    .prove(&instance, &witness)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let nark_instances = proofs
+            .iter()
+            .map(R1CSNARKInstance::from_proof)
+            .collect::<Vec<_>>();
+
+        let acc_prover = R1CSAccProver::new(
+            pk,
+            KeccakTranscript::new(b"test_r1cs_keccak_acc"),
+            KeccakTranscript::new(b"test_r1cs_keccak"),
+        );
+        let (accumulator, acc_proof) = acc_prover.prove_acc(&[], &proofs).unwrap();
+
+        let acc_verifier = R1CSAccVerifier::new(
+            vk.clone(),
+            KeccakTranscript::new(b"test_r1cs_keccak_acc"),
+            KeccakTranscript::new(b"test_r1cs_keccak"),
+        );
+        assert_eq!(
+            acc_verifier.verify(accumulator.instance(), &[], &nark_instances, &acc_proof),
+            Ok(())
+        );
+        assert_eq!(decide(&accumulator, &vk), Ok(()));
+    }
+
+    #[test]
+    fn test_r1cs_accumulation_more_inputs_than_constraints() {
+        let num_cons = 4;
+        let num_vars = 8;
+        let num_input = 6;
+        type C = Secq256k1Affine;
+
+        // The commitment key is sized to the public input,
+        // and the products are committed to under its first generators
+        let (shape, instance, witness) =
+            R1CSShape::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let (pk, vk) = setup(&shape, b"test_r1cs_more_inputs");
+        assert_eq!(vk.gens().G.len(), num_input);
+
+        let new_transcript =
+            || PoseidonTranscript::new(b"test_r1cs_more_inputs", SpongeCurve::K256);
+
+        let proofs = (0..2)
+            .map(|_| {
+                R1CSNARKProver::new(pk.clone(), new_transcript())
+                    .prove(&instance, &witness)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let verifier = Verifier::new(vk.clone(), new_transcript());
+        for proof in &proofs {
+            assert_eq!(verifier.verify(proof, &instance), Ok(()));
+            assert_eq!(verifier.verify_combined(proof, &instance), Ok(()));
+        }
+
+        let nark_instances = proofs
+            .iter()
+            .map(R1CSNARKInstance::from_proof)
+            .collect::<Vec<_>>();
+
+        let acc_prover = R1CSAccProver::<C>::new(pk, new_transcript(), new_transcript());
+        let (accumulator, acc_proof) = acc_prover.prove_acc(&[], &proofs).unwrap();
+
+        let acc_verifier =
+            R1CSAccVerifier::<C>::new(vk.clone(), new_transcript(), new_transcript());
+        assert_eq!(
+            acc_verifier.verify(accumulator.instance(), &[], &nark_instances, &acc_proof),
+            Ok(())
+        );
+        assert_eq!(decide(&accumulator, &vk), Ok(()));
     }
 }
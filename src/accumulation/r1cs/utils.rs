@@ -1,13 +1,11 @@
-use crate::accumulation::hadamard::acc_prover::HadamardAccProver;
-use crate::accumulation::hadamard::{HadamardAccProof, HadamardInstance, HadamardWitness};
-use crate::r1cs::{Pi1, R1CSNARKProof, R1CS};
-use crate::{CurveAffineExt, MultiCommitGens};
+use crate::accumulation::hadamard::HadamardInstance;
+use crate::r1cs::{squeeze_gamma, statement_transcript, Pi1};
+use crate::{CurveAffineExt, MultiCommitGens, Transcript};
 use ff::{Field, PrimeField, PrimeFieldBits};
-use halo2curves::FieldExt;
-use poseidon_transcript::sponge::SpongeCurve;
-use poseidon_transcript::transcript::PoseidonTranscript;
 use zeroize::DefaultIsZeroes;
 
+use super::acc_prover::{R1CSAccInstance, R1CSNARKInstance};
+
 pub fn pi_1_to_hadamard_instance<C>(gamma: &C::ScalarExt, pi_1: &Pi1<C>) -> HadamardInstance<C>
 where
     C: CurveAffineExt,
@@ -26,3 +24,182 @@ where
         (C_C + C_1_gamma + C_2_gamma_squared).into(),
     )
 }
+
+// Recompute the NARK challenge of a proof the way the NARK verifier derives it,
+// starting from a copy of the NARK's base transcript.
+pub fn compute_gamma<C, T>(
+    transcript: &T,
+    circuit_digest: &C::ScalarExt,
+    nark_instance: &R1CSNARKInstance<C>,
+) -> C::ScalarExt
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
+{
+    let statement = statement_transcript(transcript, circuit_digest, &nark_instance.public_input);
+    squeeze_gamma(statement, &nark_instance.pi_1)
+}
+
+// Commit to the public input of each new proof, under the first generators.
+pub fn commit_public_inputs<C>(
+    gens: &MultiCommitGens<C>,
+    nark_instances: &[R1CSNARKInstance<C>],
+) -> Vec<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    nark_instances
+        .iter()
+        .map(|inst| gens.commit_prefix(&inst.public_input, &C::ScalarExt::zero()))
+        .collect()
+}
+
+// Absorb the statement of an accumulation step: the prior accumulator instances and,
+// for each new proof, its public input, the commitment C_x to it and its Hadamard instance.
+// The challenges of the step are derived on a fork of the base transcript that has absorbed
+// this, so that a step can be checked without the ones before it.
+pub fn append_acc_statement<C, T>(
+    transcript: &mut T,
+    prior_acc_instances: &[R1CSAccInstance<C>],
+    nark_instances: &[R1CSNARKInstance<C>],
+    nark_C_xs: &[C],
+    hadamard_instances: &[HadamardInstance<C>],
+) where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
+{
+    transcript.append_scalar(&C::ScalarExt::from(prior_acc_instances.len() as u64));
+    for acc_inst in prior_acc_instances {
+        transcript.append_scalar(&acc_inst.u);
+        transcript.append_points(&[
+            acc_inst.C_x,
+            acc_inst.C_A,
+            acc_inst.C_B,
+            acc_inst.C_C,
+            acc_inst.acc_HP_x.0,
+            acc_inst.acc_HP_x.1,
+            acc_inst.acc_HP_x.2,
+        ]);
+    }
+
+    transcript.append_scalar(&C::ScalarExt::from(nark_instances.len() as u64));
+    for ((nark_inst, C_x), hadamard_inst) in nark_instances
+        .iter()
+        .zip(nark_C_xs.iter())
+        .zip(hadamard_instances.iter())
+    {
+        for x in &nark_inst.public_input {
+            transcript.append_scalar(x);
+        }
+        transcript.append_points(&[*C_x, hadamard_inst.0, hadamard_inst.1, hadamard_inst.2]);
+    }
+}
+
+// Derive the folding challenge beta once the statement and the new Hadamard accumulator
+// have been absorbed.
+pub fn compute_beta<C, T>(
+    transcript: &mut T,
+    hadamard_acc_instance: &HadamardInstance<C>,
+) -> C::ScalarExt
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
+{
+    transcript.append_points(&[
+        hadamard_acc_instance.0,
+        hadamard_acc_instance.1,
+        hadamard_acc_instance.2,
+    ]);
+
+    transcript.squeeze_challenge()
+}
+
+// Fold the constant wires of the prior accumulators and of the new statements,
+// each of which is 1: u = sum beta^j * u_j + sum beta^(m + i).
+pub fn fold_constant<C>(
+    prior_acc_instances: &[R1CSAccInstance<C>],
+    num_nark_instances: usize,
+    beta: &C::ScalarExt,
+) -> C::ScalarExt
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    let mut u = C::ScalarExt::zero();
+    let mut beta_pow = C::ScalarExt::one();
+    for acc_inst in prior_acc_instances {
+        u += acc_inst.u * beta_pow;
+        beta_pow *= beta;
+    }
+    for _ in 0..num_nark_instances {
+        u += beta_pow;
+        beta_pow *= beta;
+    }
+    u
+}
+
+// Fold the commitments of the prior accumulators and the new instances with powers of beta,
+// prior accumulators first: C_x = sum beta^j * C_x_j + sum beta^(m + i) * cm(x_i),
+// C_A = sum beta^j * C_A_j + sum beta^(m + i) * (C_A_i + gamma_i * C_A'_i), ...
+pub fn fold_commitments<C>(
+    prior_acc_instances: &[R1CSAccInstance<C>],
+    nark_instances: &[R1CSNARKInstance<C>],
+    nark_C_xs: &[C],
+    gammas: &[C::ScalarExt],
+    beta: &C::ScalarExt,
+) -> (C, C, C, C)
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    let mut C_x: C::Curve = C::identity().into();
+    let mut C_A: C::Curve = C::identity().into();
+    let mut C_B: C::Curve = C::identity().into();
+    let mut C_C: C::Curve = C::identity().into();
+
+    let mut beta_pow = C::ScalarExt::one();
+    for acc_inst in prior_acc_instances {
+        C_x += acc_inst.C_x * beta_pow;
+        C_A += acc_inst.C_A * beta_pow;
+        C_B += acc_inst.C_B * beta_pow;
+        C_C += acc_inst.C_C * beta_pow;
+        beta_pow *= beta;
+    }
+
+    for ((inst, nark_C_x), gamma) in nark_instances
+        .iter()
+        .zip(nark_C_xs.iter())
+        .zip(gammas.iter())
+    {
+        let pi_1 = &inst.pi_1;
+        C_x += *nark_C_x * beta_pow;
+        C_A += (pi_1.C_A + (pi_1.C_A_prime * gamma).into()) * beta_pow;
+        C_B += (pi_1.C_B + (pi_1.C_B_prime * gamma).into()) * beta_pow;
+        C_C += (pi_1.C_C + (pi_1.C_C_prime * gamma).into()) * beta_pow;
+        beta_pow *= beta;
+    }
+
+    (C_x.into(), C_A.into(), C_B.into(), C_C.into())
+}
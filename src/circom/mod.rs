@@ -0,0 +1,169 @@
+//! Import circuits compiled by circom, in the iden3 binary formats.
+//!
+//! Both formats start with a 4-byte magic, a `u32` version and a `u32` number of sections,
+//! followed by the sections, each a `u32` type and a `u64` size before its content.
+//! Integers are little-endian and field elements `n8`-byte little-endian integers.
+//!
+//! circom numbers its wires `(1, public outputs, public inputs, private inputs, internal)`,
+//! which is already the crate's `z = (1, public_input, witness)` layout,
+//! so wire `i` is column `i` of the A, B, C matrices.
+
+mod r1cs_file;
+mod wtns_file;
+
+pub use r1cs_file::{load_r1cs, CircomCircuit};
+pub use wtns_file::load_wtns;
+
+use ff::PrimeField;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircomError {
+    /// The file does not start with the expected magic.
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    /// The input ended before the value being read.
+    UnexpectedEnd,
+    /// A section required by the format is absent.
+    MissingSection(u32),
+    /// A section's content does not match its declared size.
+    InvalidSectionSize(u32),
+    /// The file is over another field than `C::ScalarExt`.
+    PrimeMismatch,
+    NonCanonicalScalar,
+    /// A constraint refers to a wire beyond the declared number of wires.
+    InvalidWire(u32),
+    /// The header declares more public wires than wires.
+    InvalidWireCount,
+    /// The witness does not assign every wire of the circuit.
+    WitnessLengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// Wire 0 of the witness is not the constant one.
+    InvalidConstantWire,
+    /// The witness does not satisfy the circuit.
+    Unsatisfied,
+}
+
+impl fmt::Display for CircomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "invalid magic"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported version {}", v),
+            Self::UnexpectedEnd => write!(f, "unexpected end of input"),
+            Self::MissingSection(t) => write!(f, "missing section of type {}", t),
+            Self::InvalidSectionSize(t) => {
+                write!(f, "section of type {} does not match its size", t)
+            }
+            Self::PrimeMismatch => write!(f, "the file's prime is not the scalar field modulus"),
+            Self::NonCanonicalScalar => write!(f, "non-canonical field element encoding"),
+            Self::InvalidWire(w) => write!(f, "wire {} out of range", w),
+            Self::InvalidWireCount => write!(f, "more public wires than wires"),
+            Self::WitnessLengthMismatch { expected, actual } => {
+                write!(f, "expected {} witness values, got {}", expected, actual)
+            }
+            Self::InvalidConstantWire => write!(f, "wire 0 of the witness is not one"),
+            Self::Unsatisfied => write!(f, "the witness does not satisfy the circuit"),
+        }
+    }
+}
+
+impl std::error::Error for CircomError {}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn read(&mut self, n: usize) -> Result<&'a [u8], CircomError> {
+        if self.bytes.len() < n {
+            return Err(CircomError::UnexpectedEnd);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, CircomError> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.read(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, CircomError> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.read(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    // Elements are written with the 32 bytes the prime check requires
+    fn read_scalar<F: PrimeField<Repr = [u8; 32]>>(&mut self) -> Result<F, CircomError> {
+        let mut repr = [0u8; 32];
+        repr.copy_from_slice(self.read(32)?);
+        Option::from(F::from_repr(repr)).ok_or(CircomError::NonCanonicalScalar)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+// Split a file into its sections, after checking its magic and version.
+fn read_sections<'a>(
+    bytes: &'a [u8],
+    magic: &[u8; 4],
+    versions: &[u32],
+) -> Result<Vec<(u32, &'a [u8])>, CircomError> {
+    let mut reader = ByteReader { bytes };
+    if reader.read(4).map_err(|_| CircomError::InvalidMagic)? != magic {
+        return Err(CircomError::InvalidMagic);
+    }
+
+    let version = reader.read_u32()?;
+    if !versions.contains(&version) {
+        return Err(CircomError::UnsupportedVersion(version));
+    }
+
+    let num_sections = reader.read_u32()?;
+    let mut sections = Vec::new();
+    for _ in 0..num_sections {
+        let section_type = reader.read_u32()?;
+        let size = reader.read_u64()?;
+        let size = usize::try_from(size).map_err(|_| CircomError::UnexpectedEnd)?;
+        sections.push((section_type, reader.read(size)?));
+    }
+
+    Ok(sections)
+}
+
+fn section<'a>(sections: &[(u32, &'a [u8])], section_type: u32) -> Result<&'a [u8], CircomError> {
+    sections
+        .iter()
+        .find(|(t, _)| *t == section_type)
+        .map(|(_, content)| *content)
+        .ok_or(CircomError::MissingSection(section_type))
+}
+
+// Read the `n8 || prime` field description of a header and check it is the field `F`.
+fn check_prime<F: PrimeField<Repr = [u8; 32]>>(
+    reader: &mut ByteReader<'_>,
+) -> Result<(), CircomError> {
+    let n8 = reader.read_u32()? as usize;
+    let prime = reader.read(n8)?;
+
+    // The modulus, as (-1) + 1 in little-endian bytes
+    let mut modulus = (-F::one()).to_repr();
+    for byte in modulus.iter_mut() {
+        let (sum, carry) = byte.overflowing_add(1);
+        *byte = sum;
+        if !carry {
+            break;
+        }
+    }
+
+    if prime != modulus {
+        return Err(CircomError::PrimeMismatch);
+    }
+    Ok(())
+}
@@ -0,0 +1,238 @@
+//! The `.r1cs` constraint file (version 1).
+//!
+//! ```text
+//! section 1, header       n8 u32 || prime n8 bytes || n_wires u32 || n_pub_out u32
+//!                         || n_pub_in u32 || n_prv_in u32 || n_labels u64 || n_constraints u32
+//! section 2, constraints  per constraint, the A, B, C combinations,
+//!                         each n_terms u32 || n_terms * (wire u32 || coeff n8 bytes)
+//! section 3, wire labels  n_wires * label u64
+//! ```
+//!
+//! Sections of other types (the custom gates of recent circom versions) are ignored.
+
+use super::{check_prime, read_sections, section, ByteReader, CircomError};
+use crate::r1cs::{Matrix, R1CSShape};
+use crate::CurveAffineExt;
+use ff::{PrimeField, PrimeFieldBits};
+use zeroize::DefaultIsZeroes;
+
+const HEADER: u32 = 1;
+const CONSTRAINTS: u32 = 2;
+const WIRE_TO_LABEL: u32 = 3;
+
+/// A circuit read from a `.r1cs` file.
+#[derive(Clone)]
+pub struct CircomCircuit<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub shape: R1CSShape<C>,
+    pub num_pub_out: usize,
+    pub num_pub_in: usize,
+    pub num_prv_in: usize,
+    /// The signal label of each wire, for mapping wires back to the circom source.
+    pub wire_to_label: Vec<u64>,
+}
+
+/// Parse the contents of a `.r1cs` file over `C::ScalarExt`.
+pub fn load_r1cs<C>(bytes: &[u8]) -> Result<CircomCircuit<C>, CircomError>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    let sections = read_sections(bytes, b"r1cs", &[1])?;
+
+    // Header
+
+    let mut header = ByteReader {
+        bytes: section(&sections, HEADER)?,
+    };
+    check_prime::<C::ScalarExt>(&mut header)?;
+    let num_wires = header.read_u32()? as usize;
+    let num_pub_out = header.read_u32()? as usize;
+    let num_pub_in = header.read_u32()? as usize;
+    let num_prv_in = header.read_u32()? as usize;
+    let _num_labels = header.read_u64()?;
+    let num_cons = header.read_u32()? as usize;
+    if !header.is_empty() {
+        return Err(CircomError::InvalidSectionSize(HEADER));
+    }
+
+    // Wire 0 is the constant one, and the public signals make up the public input
+    let num_input = num_pub_out + num_pub_in;
+    if num_wires < 1 + num_input + num_prv_in {
+        return Err(CircomError::InvalidWireCount);
+    }
+    let num_vars = num_wires - 1 - num_input;
+
+    // Constraints
+
+    let mut constraints = ByteReader {
+        bytes: section(&sections, CONSTRAINTS)?,
+    };
+    let mut A = vec![];
+    let mut B = vec![];
+    let mut C = vec![];
+    for row in 0..num_cons {
+        for matrix in [&mut A, &mut B, &mut C] {
+            let num_terms = constraints.read_u32()?;
+            for _ in 0..num_terms {
+                let wire = constraints.read_u32()?;
+                if wire as usize >= num_wires {
+                    return Err(CircomError::InvalidWire(wire));
+                }
+                let coeff: C::ScalarExt = constraints.read_scalar()?;
+                matrix.push((row, wire as usize, coeff));
+            }
+        }
+    }
+    if !constraints.is_empty() {
+        return Err(CircomError::InvalidSectionSize(CONSTRAINTS));
+    }
+
+    // Wire labels
+
+    let mut labels = ByteReader {
+        bytes: section(&sections, WIRE_TO_LABEL)?,
+    };
+    let wire_to_label = (0..num_wires)
+        .map(|_| labels.read_u64())
+        .collect::<Result<Vec<u64>, CircomError>>()
+        .map_err(|_| CircomError::InvalidSectionSize(WIRE_TO_LABEL))?;
+    if !labels.is_empty() {
+        return Err(CircomError::InvalidSectionSize(WIRE_TO_LABEL));
+    }
+
+    // The wires were checked against num_wires while reading the constraints
+    let matrix = |entries| Matrix::new(num_cons, num_wires, entries).unwrap();
+
+    let shape = R1CSShape {
+        A: matrix(A),
+        B: matrix(B),
+        C: matrix(C),
+        num_cons,
+        num_vars,
+        num_input,
+        constraint_labels: vec![],
+    };
+
+    Ok(CircomCircuit {
+        shape,
+        num_pub_out,
+        num_pub_in,
+        num_prv_in,
+        wire_to_label,
+    })
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::r1cs::{R1CSInstance, R1CSWitness};
+    use ff::Field;
+    use halo2curves::secq256k1::Secq256k1Affine;
+
+    type C = Secq256k1Affine;
+    type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+    pub(in crate::circom) fn modulus() -> [u8; 32] {
+        let mut modulus = (-F::one()).to_repr();
+        modulus[0] += 1;
+        modulus
+    }
+
+    pub(in crate::circom) fn push_section(bytes: &mut Vec<u8>, section_type: u32, content: &[u8]) {
+        bytes.extend_from_slice(&section_type.to_le_bytes());
+        bytes.extend_from_slice(&(content.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(content);
+    }
+
+    // out <== a * b, with out a public output and a, b private inputs.
+    // Wires: 0 = one, 1 = out, 2 = a, 3 = b
+    pub(in crate::circom) fn multiplier_r1cs(prime: [u8; 32]) -> Vec<u8> {
+        let mut header = vec![];
+        header.extend_from_slice(&32u32.to_le_bytes());
+        header.extend_from_slice(&prime);
+        for n in [4u32, 1, 0, 2] {
+            header.extend_from_slice(&n.to_le_bytes());
+        }
+        header.extend_from_slice(&4u64.to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes());
+
+        let mut constraints = vec![];
+        for wire in [2u32, 3, 1] {
+            constraints.extend_from_slice(&1u32.to_le_bytes());
+            constraints.extend_from_slice(&wire.to_le_bytes());
+            constraints.extend_from_slice(&F::one().to_repr());
+        }
+
+        let mut labels = vec![];
+        for label in 0..4u64 {
+            labels.extend_from_slice(&label.to_le_bytes());
+        }
+
+        let mut bytes = b"r1cs".to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        push_section(&mut bytes, HEADER, &header);
+        push_section(&mut bytes, CONSTRAINTS, &constraints);
+        push_section(&mut bytes, WIRE_TO_LABEL, &labels);
+        bytes
+    }
+
+    #[test]
+    fn test_load_r1cs() {
+        let circuit = load_r1cs::<C>(&multiplier_r1cs(modulus())).unwrap();
+        let shape = &circuit.shape;
+
+        assert_eq!(shape.num_cons, 1);
+        assert_eq!(shape.num_input, 1);
+        assert_eq!(shape.num_vars, 2);
+        assert_eq!(circuit.wire_to_label, vec![0, 1, 2, 3]);
+
+        let instance = R1CSInstance::new(vec![F::from(6)]);
+        let witness = R1CSWitness::new(vec![F::from(2), F::from(3)]);
+        assert!(shape.is_sat(&instance, &witness));
+
+        let wrong_witness = R1CSWitness::new(vec![F::from(2), F::from(4)]);
+        assert!(!shape.is_sat(&instance, &wrong_witness));
+    }
+
+    #[test]
+    fn test_reject_malformed_r1cs() {
+        let mut other_prime = modulus();
+        other_prime[31] ^= 1;
+        assert_eq!(
+            load_r1cs::<C>(&multiplier_r1cs(other_prime)).err(),
+            Some(CircomError::PrimeMismatch)
+        );
+
+        let bytes = multiplier_r1cs(modulus());
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'x';
+        assert_eq!(
+            load_r1cs::<C>(&bad_magic).err(),
+            Some(CircomError::InvalidMagic)
+        );
+
+        assert_eq!(
+            load_r1cs::<C>(&bytes[..bytes.len() - 1]).err(),
+            Some(CircomError::UnexpectedEnd)
+        );
+
+        // Point the first term of A at wire 4 of 4
+        let mut bad_wire = bytes.clone();
+        let constraints_start = 12 + 12 + (4 + 32 + 16 + 8 + 4) + 12;
+        bad_wire[constraints_start + 4] = 4;
+        assert_eq!(
+            load_r1cs::<C>(&bad_wire).err(),
+            Some(CircomError::InvalidWire(4))
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! The `.wtns` witness file written by circom's witness calculator (versions 1 and 2).
+//!
+//! ```text
+//! section 1, header  n8 u32 || prime n8 bytes || n_values u32
+//! section 2, values  n_values * value n8 bytes, in wire order
+//! ```
+
+use super::{check_prime, read_sections, section, ByteReader, CircomError};
+use crate::r1cs::{R1CSInstance, R1CSShape, R1CSWitness};
+use crate::CurveAffineExt;
+use ff::{Field, PrimeField, PrimeFieldBits};
+use zeroize::DefaultIsZeroes;
+
+const HEADER: u32 = 1;
+const VALUES: u32 = 2;
+
+/// Parse the contents of a `.wtns` file computed for `shape`,
+/// and split the wire values into the public input and the witness.
+pub fn load_wtns<C>(
+    bytes: &[u8],
+    shape: &R1CSShape<C>,
+) -> Result<(R1CSInstance<C>, R1CSWitness<C>), CircomError>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    let sections = read_sections(bytes, b"wtns", &[1, 2])?;
+
+    let mut header = ByteReader {
+        bytes: section(&sections, HEADER)?,
+    };
+    check_prime::<C::ScalarExt>(&mut header)?;
+    let num_values = header.read_u32()? as usize;
+    if !header.is_empty() {
+        return Err(CircomError::InvalidSectionSize(HEADER));
+    }
+
+    let num_wires = 1 + shape.num_input + shape.num_vars;
+    if num_values != num_wires {
+        return Err(CircomError::WitnessLengthMismatch {
+            expected: num_wires,
+            actual: num_values,
+        });
+    }
+
+    let mut values_reader = ByteReader {
+        bytes: section(&sections, VALUES)?,
+    };
+    let mut values = (0..num_values)
+        .map(|_| values_reader.read_scalar())
+        .collect::<Result<Vec<C::ScalarExt>, CircomError>>()?;
+    if !values_reader.is_empty() {
+        return Err(CircomError::InvalidSectionSize(VALUES));
+    }
+
+    if values[0] != C::ScalarExt::one() {
+        return Err(CircomError::InvalidConstantWire);
+    }
+
+    let witness = R1CSWitness::new(values.split_off(1 + shape.num_input));
+    let instance = R1CSInstance::new(values.split_off(1));
+
+    if !shape.is_sat(&instance, &witness) {
+        return Err(CircomError::Unsatisfied);
+    }
+
+    Ok((instance, witness))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circom::load_r1cs;
+    use crate::circom::r1cs_file::tests::{modulus, multiplier_r1cs, push_section};
+    use crate::r1cs::{setup, R1CSNARKProver, Verifier};
+    use halo2curves::secq256k1::Secq256k1Affine;
+    use poseidon_transcript::sponge::SpongeCurve;
+    use poseidon_transcript::transcript::PoseidonTranscript;
+
+    type C = Secq256k1Affine;
+    type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+    fn wtns(prime: [u8; 32], values: &[F]) -> Vec<u8> {
+        let mut header = vec![];
+        header.extend_from_slice(&32u32.to_le_bytes());
+        header.extend_from_slice(&prime);
+        header.extend_from_slice(&(values.len() as u32).to_le_bytes());
+
+        let mut content = vec![];
+        for value in values {
+            content.extend_from_slice(&value.to_repr());
+        }
+
+        let mut bytes = b"wtns".to_vec();
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        push_section(&mut bytes, HEADER, &header);
+        push_section(&mut bytes, VALUES, &content);
+        bytes
+    }
+
+    #[test]
+    fn test_load_wtns() {
+        let circuit = load_r1cs::<C>(&multiplier_r1cs(modulus())).unwrap();
+        let shape = &circuit.shape;
+
+        let values = [F::one(), F::from(6), F::from(2), F::from(3)];
+        let (instance, witness) = load_wtns(&wtns(modulus(), &values), shape).unwrap();
+        assert_eq!(instance.public_input, vec![F::from(6)]);
+        assert_eq!(witness.w, vec![F::from(2), F::from(3)]);
+
+        let (pk, vk) = setup(shape, b"test_load_wtns");
+        let mut prover = R1CSNARKProver::new(
+            pk,
+            PoseidonTranscript::new(b"test_load_wtns", SpongeCurve::K256),
+        );
+        let proof = prover.prove(&instance, &witness).unwrap();
+        let verifier = Verifier::new(
+            vk,
+            PoseidonTranscript::new(b"test_load_wtns", SpongeCurve::K256),
+        );
+        assert_eq!(verifier.verify(&proof, &instance), Ok(()));
+    }
+
+    #[test]
+    fn test_reject_invalid_wtns() {
+        let circuit = load_r1cs::<C>(&multiplier_r1cs(modulus())).unwrap();
+        let shape = &circuit.shape;
+        let values = [F::one(), F::from(6), F::from(2), F::from(3)];
+
+        let mut other_prime = modulus();
+        other_prime[31] ^= 1;
+        assert_eq!(
+            load_wtns(&wtns(other_prime, &values), shape).err(),
+            Some(CircomError::PrimeMismatch)
+        );
+
+        assert_eq!(
+            load_wtns(&wtns(modulus(), &values[..3]), shape).err(),
+            Some(CircomError::WitnessLengthMismatch {
+                expected: 4,
+                actual: 3
+            })
+        );
+
+        let wrong_product = [F::one(), F::from(7), F::from(2), F::from(3)];
+        assert_eq!(
+            load_wtns(&wtns(modulus(), &wrong_product), shape).err(),
+            Some(CircomError::Unsatisfied)
+        );
+
+        let no_one = [F::from(2), F::from(6), F::from(2), F::from(3)];
+        assert_eq!(
+            load_wtns(&wtns(modulus(), &no_one), shape).err(),
+            Some(CircomError::InvalidConstantWire)
+        );
+    }
+}
@@ -1,12 +1,15 @@
 use crate::CurveAffineExt;
 use digest::{ExtendableOutput, Input};
-use ff::{PrimeField, PrimeFieldBits};
+use ff::{Field, PrimeField, PrimeFieldBits};
+use halo2curves::FieldExt;
 use multiexp::multiexp;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use sha3::Shake256;
 use std::io::Read;
 use zeroize::DefaultIsZeroes;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MultiCommitGens<C>
 where
     C: CurveAffineExt,
@@ -25,20 +28,17 @@ where
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
 {
+    /// Derive `n + 1` generators from `label` with a try-and-increment hash-to-curve
+    /// over the SHAKE256 stream, so that no discrete log relation between them is known.
     pub fn new(n: usize, label: &[u8]) -> Self {
         let mut shake = Shake256::default();
         shake.input(label);
         shake.input(C::generator().to_bytes());
 
         let mut reader = shake.xof_result();
-        let mut gens: Vec<C> = Vec::new();
-        let mut uniform_bytes = [0u8; 128];
-        for i in 0..n + 1 {
-            reader.read_exact(&mut uniform_bytes).unwrap();
-            // TODO: Curve point from random bytes
-            // Unsafe!
-            //            gens.push((C::generator() * C::ScalarExt::from(i as u64)).into());
-            gens.push((C::generator() * C::ScalarExt::from(i as u64)).into());
+        let mut gens: Vec<C> = Vec::with_capacity(n + 1);
+        for _ in 0..n + 1 {
+            gens.push(Self::hash_to_curve(&mut reader));
         }
 
         MultiCommitGens {
@@ -47,27 +47,208 @@ where
         }
     }
 
-    pub fn clone(&self) -> Self {
-        Self {
-            h: self.h,
-            G: self.G.clone(),
+    // Map the XOF stream to a curve point: reduce 64 uniform bytes to an x-coordinate
+    // and take the even square root of x^3 + ax + b, drawing fresh bytes until it exists.
+    // The curves we target (secp256k1, secq256k1) have cofactor 1,
+    // so every point found this way lies in the prime-order group.
+    fn hash_to_curve<R: Read>(reader: &mut R) -> C {
+        let mut uniform_bytes = [0u8; 64];
+        loop {
+            reader.read_exact(&mut uniform_bytes).unwrap();
+            let x = C::Base::from_bytes_wide(&uniform_bytes);
+            let y_squared = x.square() * x + C::a() * x + C::b();
+
+            let y: Option<C::Base> = y_squared.sqrt().into();
+            if let Some(y) = y {
+                let y = if bool::from(y.is_odd()) { -y } else { y };
+                let point: Option<C> = C::from_xy(x, y).into();
+                if let Some(point) = point {
+                    if !bool::from(point.is_identity()) {
+                        return point;
+                    }
+                }
+            }
         }
     }
 
+    /// Check that the generators were derived from `label`,
+    /// so a verifier can trust a key received from someone else.
+    pub fn verify_derivation(&self, label: &[u8]) -> bool {
+        let expected = Self::new(self.G.len(), label);
+        self.G == expected.G && self.h == expected.h
+    }
+
+    /// Hash of the generators, which the NARK transcript absorbs
+    /// to bind proofs to this commitment key.
+    pub fn digest(&self) -> C::ScalarExt {
+        let mut shake = Shake256::default();
+        shake.input(b"r1cs-nark-commitment-key-digest");
+        shake.input((self.G.len() as u64).to_le_bytes());
+        for g in &self.G {
+            shake.input(g.to_bytes());
+        }
+        shake.input(self.h.to_bytes());
+
+        let mut digest_bytes = [0u8; 64];
+        shake.xof_result().read_exact(&mut digest_bytes).unwrap();
+        C::ScalarExt::from_bytes_wide(&digest_bytes)
+    }
+
+    /// Commit to `a`, which has one entry per generator.
     pub fn commit(&self, a: &[C::ScalarExt], blinder: &C::ScalarExt) -> C {
         assert_eq!(self.G.len(), a.len());
+        self.commit_prefix(a, blinder)
+    }
+
+    /// Commit to `a` under the first `a.len()` generators, as to `a` padded with zeros.
+    /// Only for vectors whose length is fixed by the context, such as the public input.
+    pub fn commit_prefix(&self, a: &[C::ScalarExt], blinder: &C::ScalarExt) -> C {
+        assert!(a.len() <= self.G.len());
 
         let pairs = self
             .G
             .iter()
-            .enumerate()
-            .map(|(i, g)| (a[i], (*g).into()))
+            .zip(a.iter())
+            .map(|(g, a_i)| (*a_i, (*g).into()))
             .collect::<Vec<(C::ScalarExt, C::Curve)>>();
 
         let mut com: C::Curve = C::identity().into();
-        com += multiexp(&pairs);
+        com += msm::<C>(&pairs);
         com += self.h * blinder;
 
         com.into()
     }
+
+    /// The key of the first `n` generators with the same blinding generator,
+    /// under which `commit` agrees with `commit_prefix` under this key.
+    pub fn prefix(&self, n: usize) -> Self {
+        assert!(n <= self.G.len());
+
+        MultiCommitGens {
+            G: self.G[..n].to_vec(),
+            h: self.h,
+        }
+    }
+}
+
+// The multiexp of `pairs`. With the `parallel` feature, split into one chunk per thread,
+// whose partial sums add up to the same point.
+pub(crate) fn msm<C>(pairs: &[(C::ScalarExt, C::Curve)]) -> C::Curve
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    #[cfg(feature = "parallel")]
+    {
+        let num_threads = rayon::current_num_threads();
+        let chunk_size = ((pairs.len() + num_threads - 1) / num_threads).max(1);
+        pairs
+            .par_chunks(chunk_size)
+            .map(multiexp)
+            .reduce(|| C::identity().into(), |a, b| a + b)
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        multiexp(pairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves::secp256k1::Secp256k1Affine;
+    use halo2curves::secq256k1::Secq256k1Affine;
+    use halo2curves::CurveAffine;
+
+    fn check_gens<C>(n: usize)
+    where
+        C: CurveAffineExt,
+        C::ScalarExt: PrimeFieldBits,
+        C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+        C::ScalarExt: DefaultIsZeroes,
+    {
+        let gens = MultiCommitGens::<C>::new(n, b"test_multi_commit_gens");
+        assert_eq!(gens.G.len(), n);
+
+        let mut all = gens.G.clone();
+        all.push(gens.h);
+        for (i, g) in all.iter().enumerate() {
+            assert!(bool::from(g.is_on_curve()));
+            assert!(!bool::from(g.is_identity()));
+            assert!(*g != C::generator());
+            for other in &all[i + 1..] {
+                assert!(g != other);
+            }
+        }
+
+        assert!(gens.verify_derivation(b"test_multi_commit_gens"));
+        assert!(!gens.verify_derivation(b"another_label"));
+
+        // Two generators swapped is not a derived key
+        let mut tampered = gens.clone();
+        tampered.G.swap(0, 1);
+        assert!(!tampered.verify_derivation(b"test_multi_commit_gens"));
+    }
+
+    #[test]
+    fn test_gens_secq256k1() {
+        check_gens::<Secq256k1Affine>(16);
+    }
+
+    #[test]
+    fn test_gens_secp256k1() {
+        check_gens::<Secp256k1Affine>(16);
+    }
+
+    #[test]
+    fn test_commit_binding_to_vector() {
+        type C = Secq256k1Affine;
+        type F = <C as CurveAffine>::ScalarExt;
+
+        let gens = MultiCommitGens::<C>::new(3, b"test_commit_binding");
+        let blinder = F::from(7);
+
+        // With generators G_i = i * G, 2 * G_1 = G_2 and these two openings collided.
+        let c1 = gens.commit(&[F::zero(), F::from(2), F::zero()], &blinder);
+        let c2 = gens.commit(&[F::zero(), F::zero(), F::one()], &blinder);
+        assert!(c1 != c2);
+    }
+
+    #[test]
+    fn test_commit_matches_naive_sum() {
+        type C = Secq256k1Affine;
+        type F = <C as CurveAffine>::ScalarExt;
+
+        // Long enough to be split across threads with the parallel feature
+        let n = 100;
+        let gens = MultiCommitGens::<C>::new(n, b"test_commit_naive");
+        let a = (0..n)
+            .map(|i| F::from(i as u64 * 3 + 1))
+            .collect::<Vec<F>>();
+        let blinder = F::from(11);
+
+        let naive = |a: &[F]| {
+            let mut com = gens.h * blinder;
+            for (g, a_i) in gens.G.iter().zip(a.iter()) {
+                com += *g * *a_i;
+            }
+            C::from(com)
+        };
+        assert_eq!(gens.commit(&a, &blinder), naive(&a));
+        assert_eq!(gens.commit_prefix(&a, &blinder), naive(&a));
+        assert_eq!(gens.commit_prefix(&a[..7], &blinder), naive(&a[..7]));
+        assert_eq!(gens.prefix(7).commit(&a[..7], &blinder), naive(&a[..7]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_commit_rejects_short_vector() {
+        type C = Secq256k1Affine;
+        type F = <C as CurveAffine>::ScalarExt;
+
+        let gens = MultiCommitGens::<C>::new(3, b"test_commit_length");
+        gens.commit(&[F::one(), F::one()], &F::zero());
+    }
 }
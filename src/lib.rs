@@ -1,11 +1,14 @@
 #![allow(non_snake_case)]
-mod accumulation;
+pub mod accumulation;
+pub mod circom;
 mod commitment;
 mod predicates;
 mod prng;
+pub mod transcript;
 mod utils;
 
 pub use commitment::MultiCommitGens;
 pub use halo2curves::CurveAffineExt;
 pub use predicates::r1cs;
 pub use prng::PRNG;
+pub use transcript::Transcript;
@@ -1,5 +1,6 @@
-use crate::{utils::hadamard_prod, CurveAffineExt, MultiCommitGens, PRNG};
+use crate::{utils::hadamard_prod, CurveAffineExt, MultiCommitGens, Transcript, PRNG};
 use ff::{PrimeField, PrimeFieldBits};
+use poseidon_transcript::transcript::PoseidonTranscript;
 use zeroize::DefaultIsZeroes;
 
 pub struct HadamardProof<C: CurveAffineExt> {
@@ -11,7 +12,7 @@ pub struct HadamardProof<C: CurveAffineExt> {
     pub w3: C::ScalarExt,
 }
 
-pub struct HadamardProver<C>
+pub struct HadamardProver<C, T = PoseidonTranscript<C>>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
@@ -19,22 +20,27 @@ where
     C::ScalarExt: DefaultIsZeroes,
 {
     gens: MultiCommitGens<C>,
-    prng: PRNG<C>,
+    prng: PRNG<C, T>,
 }
 
-impl<C> HadamardProver<C>
+impl<C, T> HadamardProver<C, T>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::Base: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
 {
-    pub fn new(gens: MultiCommitGens<C>) -> Self {
-        Self {
-            gens,
-            prng: PRNG::new(),
-        }
+    /// Draw the blinders from a PRNG on a fork of `transcript`, seeded from the
+    /// operating system's randomness.
+    pub fn new(gens: MultiCommitGens<C>, transcript: &T) -> Self {
+        Self::with_prng(gens, PRNG::new(transcript))
+    }
+
+    /// Draw the blinders from `prng` rather than from the operating system's randomness.
+    pub fn with_prng(gens: MultiCommitGens<C>, prng: PRNG<C, T>) -> Self {
+        Self { gens, prng }
     }
 
     pub fn prove(&mut self, a: &[C::ScalarExt], b: &[C::ScalarExt]) -> HadamardProof<C> {
@@ -64,6 +70,7 @@ where
 mod tests {
     use super::*;
     use halo2curves::{secq256k1::Secq256k1Affine, CurveAffine, FieldExt};
+    use poseidon_transcript::sponge::SpongeCurve;
 
     // We don't use a HadamardProof verifier in any of the protocol we implement,
     // so we just write a simple test here to make sure the proof verifies.
@@ -73,7 +80,8 @@ mod tests {
 
         let n = 10;
         let gens: MultiCommitGens<_> = MultiCommitGens::new(n, b"test_hadamard_proof_verify");
-        let mut hadamard_prover = HadamardProver::<C>::new(gens.clone());
+        let transcript = PoseidonTranscript::new(b"test_hadamard_proof_verify", SpongeCurve::K256);
+        let mut hadamard_prover = HadamardProver::<C>::new(gens.clone(), &transcript);
 
         let mut a = Vec::with_capacity(n);
         let mut b = Vec::with_capacity(n);
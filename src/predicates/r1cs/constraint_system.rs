@@ -0,0 +1,524 @@
+use super::r1cs::{Matrix, R1CSInstance, R1CSShape, R1CSWitness};
+use crate::CurveAffineExt;
+use ff::{Field, PrimeField, PrimeFieldBits};
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use zeroize::DefaultIsZeroes;
+
+/// A variable of a circuit under construction.
+///
+/// `One` is the constant wire, in column 0 of `z = (1, x_1, ..., x_l, w_1, ..., w_n)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Variable {
+    One,
+    Public(usize),
+    Private(usize),
+}
+
+/// A variable in a constraint that the constraint system did not allocate,
+/// such as a hand-built `Variable::Private(n)` past the last private variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnallocatedVariableError {
+    pub constraint: usize,
+    pub variable: Variable,
+}
+
+impl fmt::Display for UnallocatedVariableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "constraint {} refers to the unallocated variable {:?}",
+            self.constraint, self.variable
+        )
+    }
+}
+
+impl std::error::Error for UnallocatedVariableError {}
+
+/// A sum of variables scaled by constants.
+#[derive(Debug, Clone)]
+pub struct LinearCombination<C>(Vec<(Variable, C::ScalarExt)>)
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes;
+
+impl<C> LinearCombination<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub fn zero() -> Self {
+        Self(vec![])
+    }
+
+    /// The constant `c`, as a multiple of the one wire.
+    pub fn constant(c: C::ScalarExt) -> Self {
+        Self(vec![(ConstraintSystem::<C>::one(), c)])
+    }
+
+    pub fn add_term(mut self, var: Variable, coeff: C::ScalarExt) -> Self {
+        self.0.push((var, coeff));
+        self
+    }
+
+    pub fn terms(&self) -> &[(Variable, C::ScalarExt)] {
+        &self.0
+    }
+
+    // The row of a matrix this combination occupies: like terms merged,
+    // zero coefficients dropped and columns in increasing order.
+    fn to_row(&self, num_input: usize) -> Vec<(usize, C::ScalarExt)> {
+        let mut terms = self
+            .0
+            .iter()
+            .map(|(var, coeff)| {
+                let col = match var {
+                    Variable::One => 0,
+                    Variable::Public(i) => 1 + *i,
+                    Variable::Private(i) => 1 + num_input + *i,
+                };
+                (col, *coeff)
+            })
+            .collect::<Vec<(usize, C::ScalarExt)>>();
+        terms.sort_by_key(|(col, _)| *col);
+
+        let mut row: Vec<(usize, C::ScalarExt)> = Vec::with_capacity(terms.len());
+        for (col, coeff) in terms {
+            match row.last_mut() {
+                Some((last_col, last_coeff)) if *last_col == col => *last_coeff += coeff,
+                _ => row.push((col, coeff)),
+            }
+        }
+        row.retain(|(_, coeff)| !bool::from(coeff.is_zero()));
+        row
+    }
+}
+
+impl<C> From<Variable> for LinearCombination<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    fn from(var: Variable) -> Self {
+        Self(vec![(var, C::ScalarExt::one())])
+    }
+}
+
+impl<C> Add for LinearCombination<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self.0.extend(other.0);
+        self
+    }
+}
+
+impl<C> Add<Variable> for LinearCombination<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    type Output = Self;
+
+    fn add(self, var: Variable) -> Self {
+        self.add_term(var, C::ScalarExt::one())
+    }
+}
+
+impl<C> Sub for LinearCombination<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + (-other)
+    }
+}
+
+impl<C> Sub<Variable> for LinearCombination<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    type Output = Self;
+
+    fn sub(self, var: Variable) -> Self {
+        self.add_term(var, -C::ScalarExt::one())
+    }
+}
+
+impl<C> Neg for LinearCombination<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(
+            self.0
+                .into_iter()
+                .map(|(var, coeff)| (var, -coeff))
+                .collect(),
+        )
+    }
+}
+
+impl<C> Mul<C::ScalarExt> for LinearCombination<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    type Output = Self;
+
+    fn mul(self, scalar: C::ScalarExt) -> Self {
+        Self(
+            self.0
+                .into_iter()
+                .map(|(var, coeff)| (var, coeff * scalar))
+                .collect(),
+        )
+    }
+}
+
+/// Builds an R1CS together with a satisfying assignment.
+///
+/// Variables are allocated with their values, so the witness is filled in as the circuit is
+/// written, and `finalize` splits the result into the shape, the instance and the witness.
+pub struct ConstraintSystem<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    public_values: Vec<C::ScalarExt>,
+    private_values: Vec<C::ScalarExt>,
+    constraints: Vec<(
+        LinearCombination<C>,
+        LinearCombination<C>,
+        LinearCombination<C>,
+    )>,
+    labels: Vec<Option<String>>,
+}
+
+impl<C> Default for ConstraintSystem<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> ConstraintSystem<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub fn new() -> Self {
+        Self {
+            public_values: vec![],
+            private_values: vec![],
+            constraints: vec![],
+            labels: vec![],
+        }
+    }
+
+    /// The constant-one wire.
+    pub fn one() -> Variable {
+        Variable::One
+    }
+
+    pub fn alloc_public(&mut self, value: C::ScalarExt) -> Variable {
+        self.public_values.push(value);
+        Variable::Public(self.public_values.len() - 1)
+    }
+
+    pub fn alloc_private(&mut self, value: C::ScalarExt) -> Variable {
+        self.private_values.push(value);
+        Variable::Private(self.private_values.len() - 1)
+    }
+
+    /// The value a variable was allocated with.
+    pub fn value(&self, var: Variable) -> C::ScalarExt {
+        match var {
+            Variable::One => C::ScalarExt::one(),
+            Variable::Public(i) => self.public_values[i],
+            Variable::Private(i) => self.private_values[i],
+        }
+    }
+
+    /// Evaluate `lc` on the values allocated so far.
+    pub fn eval(&self, lc: &LinearCombination<C>) -> C::ScalarExt {
+        lc.0.iter().fold(C::ScalarExt::zero(), |acc, (var, coeff)| {
+            acc + self.value(*var) * coeff
+        })
+    }
+
+    /// Add the constraint `a * b = c`.
+    pub fn enforce(
+        &mut self,
+        a: LinearCombination<C>,
+        b: LinearCombination<C>,
+        c: LinearCombination<C>,
+    ) {
+        self.constraints.push((a, b, c));
+        self.labels.push(None);
+    }
+
+    /// Add the constraint `a * b = c`, named `label` in the diagnostics of `check_sat`.
+    pub fn enforce_labeled(
+        &mut self,
+        label: &str,
+        a: LinearCombination<C>,
+        b: LinearCombination<C>,
+        c: LinearCombination<C>,
+    ) {
+        self.constraints.push((a, b, c));
+        self.labels.push(Some(label.to_string()));
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// Check the constraints against the allocated values,
+    /// returning the index of the first unsatisfied constraint.
+    pub fn which_is_unsatisfied(&self) -> Option<usize> {
+        self.constraints
+            .iter()
+            .position(|(a, b, c)| self.eval(a) * self.eval(b) != self.eval(c))
+    }
+
+    /// Split the circuit into its shape, and the allocated values into
+    /// the instance (the public variables) and the witness.
+    pub fn finalize(
+        self,
+    ) -> Result<(R1CSShape<C>, R1CSInstance<C>, R1CSWitness<C>), UnallocatedVariableError> {
+        let num_input = self.public_values.len();
+        let num_vars = self.private_values.len();
+        let num_cons = self.constraints.len();
+
+        // A public variable past the last one would land in the columns of the private ones
+        for (i, (a, b, c)) in self.constraints.iter().enumerate() {
+            for (var, _) in a.0.iter().chain(b.0.iter()).chain(c.0.iter()) {
+                let allocated = match var {
+                    Variable::One => true,
+                    Variable::Public(j) => *j < num_input,
+                    Variable::Private(j) => *j < num_vars,
+                };
+                if !allocated {
+                    return Err(UnallocatedVariableError {
+                        constraint: i,
+                        variable: *var,
+                    });
+                }
+            }
+        }
+
+        let mut A = vec![];
+        let mut B = vec![];
+        let mut C = vec![];
+
+        for (i, (a, b, c)) in self.constraints.iter().enumerate() {
+            A.extend(a.to_row(num_input).into_iter().map(|(j, v)| (i, j, v)));
+            B.extend(b.to_row(num_input).into_iter().map(|(j, v)| (i, j, v)));
+            C.extend(c.to_row(num_input).into_iter().map(|(j, v)| (i, j, v)));
+        }
+
+        let num_cols = 1 + num_input + num_vars;
+        let matrix = |entries| {
+            Matrix::new(num_cons, num_cols, entries).expect("every variable is allocated")
+        };
+
+        let shape = R1CSShape {
+            A: matrix(A),
+            B: matrix(B),
+            C: matrix(C),
+            num_cons,
+            num_vars,
+            num_input,
+            constraint_labels: if self.labels.iter().all(Option::is_none) {
+                vec![]
+            } else {
+                self.labels
+            },
+        };
+
+        Ok((
+            shape,
+            R1CSInstance::new(self.public_values),
+            R1CSWitness::new(self.private_values),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r1cs::{
+        setup, R1CSNARKProver, SatisfiabilityError, UnsatisfiedConstraint, Verifier,
+    };
+    use halo2curves::secq256k1::Secq256k1Affine;
+    use poseidon_transcript::sponge::SpongeCurve;
+    use poseidon_transcript::transcript::PoseidonTranscript;
+
+    type C = Secq256k1Affine;
+    type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+    // x^3 + x + 5 = out, with out public and x private
+    fn cubic(x: F) -> ConstraintSystem<C> {
+        let mut cs = ConstraintSystem::<C>::new();
+
+        let x_var = cs.alloc_private(x);
+        let x_sq = cs.alloc_private(x * x);
+        let x_cu = cs.alloc_private(x * x * x);
+        let out = cs.alloc_public(x * x * x + x + F::from(5));
+
+        cs.enforce_labeled("x_sq", x_var.into(), x_var.into(), x_sq.into());
+        cs.enforce_labeled("x_cu", x_sq.into(), x_var.into(), x_cu.into());
+        cs.enforce_labeled(
+            "out",
+            LinearCombination::from(x_cu) + x_var + LinearCombination::constant(F::from(5)),
+            ConstraintSystem::<C>::one().into(),
+            out.into(),
+        );
+
+        cs
+    }
+
+    #[test]
+    fn test_constraint_system() {
+        let cs = cubic(F::from(3));
+        assert_eq!(cs.num_constraints(), 3);
+        assert_eq!(cs.which_is_unsatisfied(), None);
+
+        let (shape, instance, witness) = cs.finalize().unwrap();
+        assert_eq!(shape.num_input, 1);
+        assert_eq!(shape.num_vars, 3);
+        assert_eq!(instance.public_input, vec![F::from(35)]);
+        assert!(shape.is_sat(&instance, &witness));
+
+        let mut wrong_instance = instance.clone();
+        wrong_instance.public_input[0] = F::from(36);
+        assert!(!shape.is_sat(&wrong_instance, &witness));
+
+        let (pk, vk) = setup(&shape, b"test_constraint_system");
+        let mut prover = R1CSNARKProver::new(
+            pk,
+            PoseidonTranscript::new(b"test_constraint_system", SpongeCurve::K256),
+        );
+        let proof = prover.prove(&instance, &witness).unwrap();
+
+        let verifier = Verifier::new(
+            vk,
+            PoseidonTranscript::new(b"test_constraint_system", SpongeCurve::K256),
+        );
+        assert_eq!(verifier.verify(&proof, &instance), Ok(()));
+    }
+
+    #[test]
+    fn test_unsatisfied_constraint() {
+        let mut cs = cubic(F::from(3));
+        let x_var = Variable::Private(0);
+        let wrong = cs.alloc_private(F::from(10));
+        cs.enforce(x_var.into(), x_var.into(), wrong.into());
+        assert_eq!(cs.which_is_unsatisfied(), Some(3));
+
+        let (shape, instance, witness) = cs.finalize().unwrap();
+        assert_eq!(
+            shape.check_sat(&instance, &witness),
+            Err(SatisfiabilityError::Unsatisfied(vec![
+                UnsatisfiedConstraint {
+                    index: 3,
+                    Az: F::from(3),
+                    Bz: F::from(3),
+                    Cz: F::from(10),
+                    label: None,
+                }
+            ]))
+        );
+
+        // A wrong output also breaks the labeled constraint computing it
+        let mut wrong_instance = instance.clone();
+        wrong_instance.public_input[0] = F::from(36);
+        match shape.check_sat(&wrong_instance, &witness) {
+            Err(SatisfiabilityError::Unsatisfied(unsatisfied)) => {
+                let indices = unsatisfied.iter().map(|c| c.index).collect::<Vec<_>>();
+                assert_eq!(indices, vec![2, 3]);
+                assert_eq!(unsatisfied[0].label, Some("out".to_string()));
+                assert_eq!(unsatisfied[0].Cz, F::from(36));
+            }
+            other => panic!("expected unsatisfied constraints, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_finalize_rejects_unallocated_variable() {
+        let mut cs = cubic(F::from(3));
+        let x_var = Variable::Private(0);
+        cs.enforce(x_var.into(), Variable::Private(3).into(), x_var.into());
+        assert_eq!(
+            cs.finalize().err(),
+            Some(UnallocatedVariableError {
+                constraint: 3,
+                variable: Variable::Private(3),
+            })
+        );
+
+        // One past the public input is still inside the matrix, among the private columns
+        let mut cs = cubic(F::from(3));
+        cs.enforce(x_var.into(), Variable::Public(1).into(), x_var.into());
+        assert_eq!(
+            cs.finalize().err(),
+            Some(UnallocatedVariableError {
+                constraint: 3,
+                variable: Variable::Public(1),
+            })
+        );
+    }
+
+    #[test]
+    fn test_linear_combination_rows() {
+        let x = Variable::Private(0);
+        let y = Variable::Public(1);
+
+        // 2x + y - x - x collapses to y, which sits in column 2 after the constant
+        let lc = LinearCombination::<C>::from(x) * F::from(2) + y - x - x;
+        assert_eq!(lc.to_row(2), vec![(2, F::one())]);
+
+        // Private variables come after the constant and all the public ones
+        let lc = LinearCombination::<C>::from(x) + LinearCombination::constant(F::from(7));
+        assert_eq!(lc.to_row(2), vec![(0, F::from(7)), (3, F::one())]);
+    }
+}
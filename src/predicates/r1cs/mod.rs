@@ -1,16 +1,29 @@
 #![allow(non_snake_case)]
+mod constraint_system;
 mod prover;
 mod r1cs;
+mod serialization;
+mod setup;
 mod verifier;
 
+pub use constraint_system::{
+    ConstraintSystem, LinearCombination, UnallocatedVariableError, Variable,
+};
 pub use prover::R1CSNARKProver;
-pub use r1cs::R1CS;
-pub use verifier::Verifier;
+pub(crate) use r1cs::z_vector;
+pub use r1cs::{
+    Matrix, MatrixError, R1CSInstance, R1CSShape, R1CSWitness, SatisfiabilityError,
+    UnsatisfiedConstraint,
+};
+pub use serialization::{DecodingError, PROOF_VERSION};
+pub use setup::{setup, ProvingKey, VerifyingKey};
+pub use verifier::{BatchVerificationError, VerificationError, Verifier};
 
-use crate::CurveAffineExt;
+use crate::{CurveAffineExt, Transcript};
 use ff::{PrimeField, PrimeFieldBits};
 use zeroize::DefaultIsZeroes;
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Pi1<C>
 where
     C: CurveAffineExt,
@@ -28,6 +41,7 @@ where
     pub C_2: C,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Pi2<C>
 where
     C: CurveAffineExt,
@@ -42,6 +56,7 @@ where
     pub sigma_O: C::ScalarExt,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct R1CSNARKProof<C>
 where
     C: CurveAffineExt,
@@ -54,11 +69,78 @@ where
     pub pi_2: Pi2<C>,
 }
 
+impl<C> Pi1<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub(crate) fn points(&self) -> [C; 8] {
+        [
+            self.C_A,
+            self.C_B,
+            self.C_C,
+            self.C_A_prime,
+            self.C_B_prime,
+            self.C_C_prime,
+            self.C_1,
+            self.C_2,
+        ]
+    }
+}
+
+// Fork the base transcript for one proof and absorb the proof's statement.
+// Every proof is derived on its own fork, so a transcript held across many proofs
+// is never advanced by them and the proofs are independent.
+// Strong Fiat-Shamir: absorb the statement (the circuit digest, which covers the
+// commitment key, and the public input) before the prover's first message,
+// so that gamma is bound to what the proof claims.
+pub(crate) fn statement_transcript<C, T>(
+    base_transcript: &T,
+    circuit_digest: &C::ScalarExt,
+    public_input: &[C::ScalarExt],
+) -> T
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
+{
+    let mut transcript = base_transcript.fork(b"r1cs-nark-proof");
+    transcript.append_scalar(circuit_digest);
+    for x in public_input {
+        transcript.append_scalar(x);
+    }
+    transcript
+}
+
+// Derive the NARK challenge gamma from the statement transcript of a proof and its first
+// message. The prover, the verifier and the accumulation prover and verifier all go
+// through this, so that they agree on it.
+pub(crate) fn squeeze_gamma<C, T>(mut statement_transcript: T, pi_1: &Pi1<C>) -> C::ScalarExt
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
+{
+    statement_transcript.append_points(&pi_1.points());
+    statement_transcript.squeeze_challenge()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::commitment::MultiCommitGens;
-    use crate::r1cs::{R1CSNARKProver, Verifier};
+    use crate::prng::MaxRng;
+    use crate::transcript::{KeccakTranscript, Sha3Transcript};
+    use crate::PRNG;
+    use ff::Field;
     use halo2curves::secq256k1::Secq256k1Affine;
     use poseidon_transcript::sponge::SpongeCurve;
     use poseidon_transcript::transcript::PoseidonTranscript;
@@ -72,18 +154,445 @@ mod tests {
         type C = Secq256k1Affine;
 
         let prover_transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
-        let r1cs = R1CS::<C>::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let (shape, instance, witness) =
+            R1CSShape::<C>::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+
+        assert!(shape.is_sat(&instance, &witness));
+
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+        let mut prover = R1CSNARKProver::new(pk, prover_transcript);
+
+        let proof = prover.prove(&instance, &witness).unwrap();
+
+        let verifier_transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+        let verifier = Verifier::new(vk, verifier_transcript);
+
+        assert_eq!(verifier.verify(&proof, &instance), Ok(()));
+    }
+
+    #[test]
+    pub fn test_r1cs_nizk_many_proofs() {
+        type C = Secq256k1Affine;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(16, 16, 4);
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+        let new_transcript = || PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+
+        // One prover and one verifier for all proofs
+        let mut prover = R1CSNARKProver::new(pk, new_transcript());
+        let proofs = (0..3)
+            .map(|_| prover.prove(&instance, &witness).unwrap())
+            .collect::<Vec<_>>();
+        let verifier = Verifier::new(vk.clone(), new_transcript());
+
+        for proof in &proofs {
+            assert_eq!(verifier.verify(proof, &instance), Ok(()));
+            assert_eq!(
+                Verifier::new(vk.clone(), new_transcript()).verify(proof, &instance),
+                Ok(())
+            );
+        }
+        assert!(proofs[0] != proofs[1]);
+    }
+
+    #[test]
+    pub fn test_r1cs_nizk_rejects_invalid_proof() {
+        let num_cons = 16;
+        let num_vars = 16;
+        let num_input = 4;
+
+        type C = Secq256k1Affine;
+
+        let (shape, instance, witness) =
+            R1CSShape::<C>::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+
+        let new_verifier = || {
+            let transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+            Verifier::new(vk.clone(), transcript)
+        };
+
+        let prover_transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+        let mut prover = R1CSNARKProver::new(pk, prover_transcript);
+        let mut proof = prover.prove(&instance, &witness).unwrap();
+
+        proof.pi_2.sigma_A += <C as halo2curves::CurveAffine>::ScalarExt::one();
+        assert_eq!(
+            new_verifier().verify(&proof, &instance),
+            Err(VerificationError::CommSAMismatch)
+        );
+
+        assert_eq!(
+            new_verifier().verify(
+                &proof,
+                &R1CSInstance::new(instance.public_input[1..].to_vec())
+            ),
+            Err(VerificationError::InvalidPublicInputLength {
+                expected: num_input,
+                actual: num_input - 1
+            })
+        );
+
+        let mut other_instance = instance.clone();
+        other_instance.public_input[0] += <C as halo2curves::CurveAffine>::ScalarExt::one();
+        assert_eq!(
+            new_verifier().verify(&proof, &other_instance),
+            Err(VerificationError::PublicInputMismatch)
+        );
+
+        proof.pi_2.s.pop();
+        assert_eq!(
+            new_verifier().verify(&proof, &instance),
+            Err(VerificationError::InvalidSLength {
+                expected: num_vars,
+                actual: num_vars - 1
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_r1cs_nizk_combined_check() {
+        type C = Secq256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(16, 16, 4);
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+
+        let prover_transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+        let mut prover = R1CSNARKProver::new(pk, prover_transcript);
+        let proof = prover.prove(&instance, &witness).unwrap();
+
+        let verify_combined = |proof: &R1CSNARKProof<C>| {
+            let transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+            Verifier::new(vk.clone(), transcript).verify_combined(proof, &instance)
+        };
+
+        assert_eq!(verify_combined(&proof), Ok(()));
+
+        // Each tampered proof is rejected with the error of the separate checks
+        let mut bad_sigma_B = proof.clone();
+        bad_sigma_B.pi_2.sigma_B += F::one();
+        assert_eq!(
+            verify_combined(&bad_sigma_B),
+            Err(VerificationError::CommSBMismatch)
+        );
+
+        let mut bad_C_C_prime = proof.clone();
+        bad_C_C_prime.pi_1.C_C_prime = proof.pi_1.C_A_prime;
+        assert_eq!(
+            verify_combined(&bad_C_C_prime),
+            Err(VerificationError::CommSCMismatch)
+        );
+
+        let mut bad_sigma_O = proof.clone();
+        bad_sigma_O.pi_2.sigma_O += F::one();
+        assert_eq!(
+            verify_combined(&bad_sigma_O),
+            Err(VerificationError::HadamardMismatch)
+        );
+
+        let mut bad_s = proof.clone();
+        bad_s.pi_2.s[0] += F::one();
+        assert!(verify_combined(&bad_s).is_err());
+    }
+
+    #[test]
+    pub fn test_r1cs_nizk_verify_batch() {
+        type C = Secq256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(16, 16, 4);
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+
+        let proofs = (0..3)
+            .map(|_| {
+                let transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+                R1CSNARKProver::new(pk.clone(), transcript)
+                    .prove(&instance, &witness)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let verifier = Verifier::new(
+            vk,
+            PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256),
+        );
+        let batch = |proofs: &[R1CSNARKProof<C>]| {
+            proofs
+                .iter()
+                .map(|proof| (proof, &instance))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(verifier.verify_batch(&batch(&proofs)), Ok(()));
+        assert_eq!(verifier.verify_batch(&[]), Ok(()));
+
+        let mut bad_proofs = proofs.clone();
+        bad_proofs[1].pi_2.sigma_C += F::one();
+        assert_eq!(
+            verifier.verify_batch(&batch(&bad_proofs)),
+            Err(BatchVerificationError {
+                index: Some(1),
+                error: VerificationError::CommSCMismatch
+            })
+        );
+
+        let short_instance = R1CSInstance::new(instance.public_input[1..].to_vec());
+        assert_eq!(
+            verifier.verify_batch(&[(&proofs[0], &instance), (&proofs[1], &short_instance)]),
+            Err(BatchVerificationError {
+                index: Some(1),
+                error: VerificationError::InvalidPublicInputLength {
+                    expected: 4,
+                    actual: 3
+                }
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_r1cs_nizk_seeded_prover() {
+        type C = Secq256k1Affine;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(16, 16, 4);
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+
+        let prove = |seed: [u8; 32]| {
+            let transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+            let prng = PRNG::from_seed_insecure(&transcript, seed);
+            R1CSNARKProver::with_prng(pk.clone(), transcript, prng)
+                .prove(&instance, &witness)
+                .unwrap()
+        };
+
+        // The same seed reproduces the proof
+        let proof = prove([1; 32]);
+        assert_eq!(proof, prove([1; 32]));
+        assert!(proof != prove([2; 32]));
+
+        let transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+        assert_eq!(
+            Verifier::new(vk, transcript).verify(&proof, &instance),
+            Ok(())
+        );
+    }
 
-        assert!(r1cs.is_sat(&r1cs.witness, &r1cs.public_input));
+    #[test]
+    pub fn test_r1cs_nizk_hedged_prover() {
+        type C = Secq256k1Affine;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(16, 16, 4);
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+
+        let prove = |seed: [u8; 32]| {
+            let transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+            let prng = PRNG::from_seed_insecure(&transcript, seed);
+            R1CSNARKProver::with_prng(pk.clone(), transcript, prng)
+                .hedged()
+                .prove(&instance, &witness)
+                .unwrap()
+        };
+
+        // A repeated seed no longer repeats the blinders
+        let proof = prove([1; 32]);
+        assert!(proof.pi_1 != prove([1; 32]).pi_1);
 
-        let gens = MultiCommitGens::<C>::new(num_cons, b"r1cs-nark");
-        let mut prover = R1CSNARKProver::new(r1cs.clone(), prover_transcript, gens.clone());
+        let transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+        assert_eq!(
+            Verifier::new(vk, transcript).verify(&proof, &instance),
+            Ok(())
+        );
+    }
+
+    #[test]
+    pub fn test_r1cs_nizk_hash_transcripts() {
+        type C = Secq256k1Affine;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(16, 16, 4);
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+
+        let mut prover = R1CSNARKProver::new(pk, Sha3Transcript::new(b"test-r1cs-nizk"));
+        let proof = prover.prove(&instance, &witness).unwrap();
+
+        let verifier = Verifier::new(vk.clone(), Sha3Transcript::new(b"test-r1cs-nizk"));
+        assert_eq!(verifier.verify(&proof, &instance), Ok(()));
+
+        // The challenges differ under another hash
+        let verifier = Verifier::new(vk, KeccakTranscript::new(b"test-r1cs-nizk"));
+        assert!(verifier.verify(&proof, &instance).is_err());
+    }
+
+    #[test]
+    pub fn test_prove_checked() {
+        type C = Secq256k1Affine;
 
-        let proof = prover.prove(&r1cs.witness, &r1cs.public_input);
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(16, 16, 4);
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+
+        let prover_transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+        let mut prover = R1CSNARKProver::new(pk, prover_transcript);
+        let proof = prover.prove_checked(&instance, &witness).unwrap();
 
         let verifier_transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
-        let mut verifier = Verifier::new(r1cs.clone(), verifier_transcript, gens.clone());
+        let verifier = Verifier::new(vk, verifier_transcript);
+        assert_eq!(verifier.verify(&proof, &instance), Ok(()));
+
+        let mut bad_witness = witness.clone();
+        bad_witness.w[0] += <C as halo2curves::CurveAffine>::ScalarExt::one();
+        assert!(matches!(
+            prover.prove_checked(&instance, &bad_witness),
+            Err(SatisfiabilityError::Unsatisfied(_))
+        ));
+
+        // An assignment of the wrong shape is an error, not a panic
+        let mut short_witness = witness.clone();
+        short_witness.w.pop();
+        assert_eq!(
+            prover.prove(&instance, &short_witness),
+            Err(SatisfiabilityError::InvalidWitnessLength {
+                expected: 16,
+                actual: 15
+            })
+        );
+        let mut long_instance = instance.clone();
+        long_instance
+            .public_input
+            .push(<C as halo2curves::CurveAffine>::ScalarExt::one());
+        assert_eq!(
+            prover.prove(&long_instance, &witness),
+            Err(SatisfiabilityError::InvalidPublicInputLength {
+                expected: 4,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    pub fn test_r1cs_nizk_bound_to_statement() {
+        let num_cons = 16;
+        let num_vars = 16;
+        let num_input = 4;
+
+        type C = Secq256k1Affine;
+
+        let (shape, instance, witness) =
+            R1CSShape::<C>::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+
+        let prover_transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+        let mut prover = R1CSNARKProver::new(pk, prover_transcript);
+        let proof = prover.prove(&instance, &witness).unwrap();
+
+        // The proof is presented with the public input it is checked against
+        let verify = |vk: &VerifyingKey<C>, instance: &R1CSInstance<C>| {
+            let mut proof = proof.clone();
+            proof.public_input = instance.public_input.clone();
+            let transcript = PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+            Verifier::new(vk.clone(), transcript).verify(&proof, instance)
+        };
+
+        assert_eq!(verify(&vk, &instance), Ok(()));
+
+        // Another circuit of the same dimensions, satisfied by the same witness
+        let mut other_shape = shape.clone();
+        std::mem::swap(&mut other_shape.A, &mut other_shape.B);
+        assert!(other_shape.is_sat(&instance, &witness));
+        let (_, other_vk) = setup(&other_shape, b"r1cs-nark");
+        assert!(verify(&other_vk, &instance).is_err());
+
+        // Another public input
+        let mut other_input = instance.clone();
+        other_input.public_input[0] += <C as halo2curves::CurveAffine>::ScalarExt::one();
+        assert!(verify(&vk, &other_input).is_err());
+
+        // Another commitment key
+        let other_gens = MultiCommitGens::<C>::new(num_cons, b"another-key");
+        let other_vk = VerifyingKey::from_gens(&shape, other_gens, b"another-key").unwrap();
+        assert!(verify(&other_vk, &instance).is_err());
+    }
+
+    #[test]
+    pub fn test_r1cs_nizk_gamma_bound_to_statement() {
+        type C = Secq256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        // A public input that no constraint reads: changing it leaves A(x, s), B(x, s)
+        // and C(x, s), and with them every commitment equation, as they are,
+        // so only the binding of gamma to the statement can reject the proof
+        let mut cs = ConstraintSystem::<C>::new();
+        let x = cs.alloc_private(F::from(3));
+        let x_sq = cs.alloc_private(F::from(9));
+        cs.alloc_public(F::from(7));
+        cs.enforce(x.into(), x.into(), x_sq.into());
+        let (shape, instance, witness) = cs.finalize().unwrap();
+
+        let mut other_instance = instance.clone();
+        other_instance.public_input[0] = F::from(8);
+        assert!(shape.is_sat(&other_instance, &witness));
+
+        let (pk, vk) = setup(&shape, b"r1cs-nark");
+        let new_transcript = || PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+        let proof = R1CSNARKProver::new(pk, new_transcript())
+            .prove(&instance, &witness)
+            .unwrap();
+
+        let verifier = Verifier::new(vk.clone(), new_transcript());
+        assert_eq!(verifier.verify(&proof, &instance), Ok(()));
+        let mut other_proof = proof.clone();
+        other_proof.public_input = other_instance.public_input.clone();
+        assert!(verifier.verify(&other_proof, &other_instance).is_err());
+
+        // The same first message gets another challenge under another digest or input
+        let base = new_transcript();
+        let gamma = |digest: &F, public_input: &[F]| {
+            squeeze_gamma(
+                statement_transcript(&base, digest, public_input),
+                &proof.pi_1,
+            )
+        };
+        let expected = gamma(vk.digest(), &instance.public_input);
+        assert_eq!(expected, verifier.challenge(&proof, &instance));
+        assert!(gamma(&(*vk.digest() + F::one()), &instance.public_input) != expected);
+        assert!(gamma(vk.digest(), &other_instance.public_input) != expected);
+    }
+
+    #[test]
+    pub fn test_r1cs_nizk_hedge_binds_statement() {
+        type C = Secq256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(16, 16, 4);
+        let new_transcript = || PoseidonTranscript::new(b"test-r1cs-nizk", SpongeCurve::K256);
+
+        // The mask r of the witness in a proof under a fixed seed and fixed fresh randomness,
+        // recovered from s = w + gamma * r
+        let mask = |label: &[u8], hedged: bool| {
+            let (pk, vk) = setup(&shape, label);
+            let prng = PRNG::from_seed_insecure(&new_transcript(), [7; 32]);
+            let mut prover = R1CSNARKProver::with_prng(pk, new_transcript(), prng);
+            if hedged {
+                prover = prover.hedged();
+            }
+            let proof = prover
+                .prove_with_rng(&mut MaxRng, &instance, &witness)
+                .unwrap();
+
+            let gamma = Verifier::new(vk, new_transcript()).challenge(&proof, &instance);
+            let gamma_inv = gamma.invert().unwrap();
+            proof
+                .pi_2
+                .s
+                .iter()
+                .zip(witness.w.iter())
+                .map(|(s_i, w_i)| (*s_i - w_i) * gamma_inv)
+                .collect::<Vec<F>>()
+        };
 
-        verifier.verify(&proof, &r1cs.public_input);
+        // Two circuits with the same witness and public input, under different keys.
+        // Without hedging the mask only depends on the seed
+        assert_eq!(mask(b"r1cs-nark-a", false), mask(b"r1cs-nark-b", false));
+        // Hedged, the statement enters the PRNG
+        assert!(mask(b"r1cs-nark-a", true) != mask(b"r1cs-nark-b", true));
+        assert_eq!(mask(b"r1cs-nark-a", true), mask(b"r1cs-nark-a", true));
     }
 }
@@ -1,65 +1,131 @@
-use super::r1cs::R1CS;
-use crate::commitment::MultiCommitGens;
-use crate::r1cs::{Pi1, Pi2, R1CSNARKProof};
+use super::r1cs::{z_vector, R1CSInstance, R1CSWitness, SatisfiabilityError};
+use super::setup::ProvingKey;
+use crate::r1cs::{squeeze_gamma, statement_transcript, Pi1, Pi2, R1CSNARKProof};
 use crate::utils::hadamard_prod;
 use crate::CurveAffineExt;
+use crate::Transcript;
 use crate::PRNG;
 use ff::{Field, PrimeField, PrimeFieldBits};
 use poseidon_transcript::transcript::PoseidonTranscript;
+use rand_core::{CryptoRng, OsRng, RngCore};
 use zeroize::DefaultIsZeroes;
 
-pub struct R1CSNARKProver<C>
+pub struct R1CSNARKProver<C, T = PoseidonTranscript<C>>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
 {
-    pub r1cs: R1CS<C>,
-    pub transcript: PoseidonTranscript<C>,
-    pub prng: PRNG<C>,
-    pub comm_gens: MultiCommitGens<C>,
+    pub pk: ProvingKey<C>,
+    /// The base transcript. Each proof is derived on a fork of it, so it stays as it is
+    /// across calls to `prove`.
+    pub transcript: T,
+    pub prng: PRNG<C, T>,
+    hedged: bool,
 }
 
-impl<C> R1CSNARKProver<C>
+impl<C, T> R1CSNARKProver<C, T>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::Base: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
 {
-    pub fn new(
-        r1cs: R1CS<C>,
-        transcript: PoseidonTranscript<C>,
-        comm_gens: MultiCommitGens<C>,
-    ) -> Self {
-        let prng = PRNG::new();
+    pub fn new(pk: ProvingKey<C>, transcript: T) -> Self {
+        let prng = PRNG::new(&transcript);
+        Self::with_prng(pk, transcript, prng)
+    }
+
+    /// Draw the blinders and the mask of the witness from `prng` rather than
+    /// from the operating system's randomness.
+    pub fn with_prng(pk: ProvingKey<C>, transcript: T, prng: PRNG<C, T>) -> Self {
         Self {
-            r1cs,
+            pk,
             transcript,
             prng,
-            comm_gens,
+            hedged: false,
         }
     }
 
+    /// Before each proof, mix fresh OS randomness, a hash of the witness and public input,
+    /// and a challenge of the proof's transcript after its statement into the PRNG.
+    ///
+    /// The blinders and the mask of the witness then stay hidden even if the PRNG's seed
+    /// repeats or the OS randomness fails, so that `s = w + gamma * r` does not reveal `w`.
+    pub fn hedged(mut self) -> Self {
+        self.hedged = true;
+        self
+    }
+
+    /// Check that the assignment satisfies the circuit before proving,
+    /// rather than producing a proof that will not verify.
+    pub fn prove_checked(
+        &mut self,
+        instance: &R1CSInstance<C>,
+        witness: &R1CSWitness<C>,
+    ) -> Result<R1CSNARKProof<C>, SatisfiabilityError<C::ScalarExt>> {
+        self.pk.shape().check_sat(instance, witness)?;
+        self.prove(instance, witness)
+    }
+
+    /// Prove that `witness` satisfies the circuit for the public input of `instance`.
+    /// Only the lengths of the assignment are checked, and a mismatch is returned as an error;
+    /// an assignment that does not satisfy the constraints gives a proof that does not verify.
     pub fn prove(
         &mut self,
-        witness: &[C::ScalarExt],
-        public_input: &Vec<C::ScalarExt>,
-    ) -> R1CSNARKProof<C> {
-        let num_cons = self.r1cs.num_cons;
-        let num_vars = self.r1cs.num_vars;
+        instance: &R1CSInstance<C>,
+        witness: &R1CSWitness<C>,
+    ) -> Result<R1CSNARKProof<C>, SatisfiabilityError<C::ScalarExt>> {
+        self.prove_with_rng(&mut OsRng, instance, witness)
+    }
 
-        assert_eq!(witness.len(), num_vars);
+    // Like `prove`, with `rng` as the source of the fresh randomness of a hedged prover
+    pub(crate) fn prove_with_rng<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        instance: &R1CSInstance<C>,
+        witness: &R1CSWitness<C>,
+    ) -> Result<R1CSNARKProof<C>, SatisfiabilityError<C::ScalarExt>> {
+        let public_input = &instance.public_input;
+        let witness = &witness.w;
+
+        let shape = self.pk.shape();
+        let gens = self.pk.gens();
+        let num_vars = shape.num_vars;
+
+        if public_input.len() != shape.num_input {
+            return Err(SatisfiabilityError::InvalidPublicInputLength {
+                expected: shape.num_input,
+                actual: public_input.len(),
+            });
+        }
+        if witness.len() != num_vars {
+            return Err(SatisfiabilityError::InvalidWitnessLength {
+                expected: num_vars,
+                actual: witness.len(),
+            });
+        }
+
+        let transcript = statement_transcript(&self.transcript, self.pk.digest(), public_input);
+
+        if self.hedged {
+            // Squeezing a copy leaves the proof's transcript untouched
+            let statement_challenge = transcript.clone().squeeze_challenge();
+            self.prng.hedge(
+                rng,
+                &[witness.as_slice(), public_input.as_slice()],
+                &statement_challenge,
+            );
+        }
 
         // Prove following the steps described in Section 8.1 of
 
         // Step 1
 
-        let mut z = Vec::with_capacity(witness.len() + public_input.len());
-        z.extend(public_input.clone());
-        z.extend(witness);
+        let z = z_vector::<C>(C::ScalarExt::one(), public_input, witness);
 
         // Step 2
 
@@ -67,16 +133,14 @@ where
 
         // Step 3
 
-        let z_A = self.r1cs.A.mul_vector(num_cons, &z);
-        let z_B = self.r1cs.B.mul_vector(num_cons, &z);
-        let z_C = self.r1cs.C.mul_vector(num_cons, &z);
-
-        let mut padded_r = vec![C::ScalarExt::zero(); public_input.len()];
-        padded_r.extend_from_slice(&r);
+        // Only the witness is masked: the constant and the public input stay as they are
+        let padded_r = z_vector::<C>(
+            C::ScalarExt::zero(),
+            &vec![C::ScalarExt::zero(); public_input.len()],
+            &r,
+        );
 
-        let r_A = self.r1cs.A.mul_vector(num_cons, &padded_r);
-        let r_B = self.r1cs.B.mul_vector(num_cons, &padded_r);
-        let r_C = self.r1cs.C.mul_vector(num_cons, &padded_r);
+        let ((z_A, z_B, z_C), (r_A, r_B, r_C)) = shape.multiply_vec_pair(&z, &padded_r);
 
         // Step 4
 
@@ -86,17 +150,18 @@ where
         let w_B = w[1];
         let w_C = w[2];
 
-        let C_A = self.comm_gens.commit(&z_A, &w_A);
-        let C_B = self.comm_gens.commit(&z_B, &w_B);
-        let C_C = self.comm_gens.commit(&z_C, &w_C);
+        // The key also covers the public input, which may be longer than the products
+        let C_A = gens.commit_prefix(&z_A, &w_A);
+        let C_B = gens.commit_prefix(&z_B, &w_B);
+        let C_C = gens.commit_prefix(&z_C, &w_C);
 
         let w_A_prime = w[3];
         let w_B_prime = w[4];
         let w_C_prime = w[5];
 
-        let C_A_prime = self.comm_gens.commit(&r_A, &w_A_prime);
-        let C_B_prime = self.comm_gens.commit(&r_B, &w_B_prime);
-        let C_C_prime = self.comm_gens.commit(&r_C, &w_C_prime);
+        let C_A_prime = gens.commit_prefix(&r_A, &w_A_prime);
+        let C_B_prime = gens.commit_prefix(&r_B, &w_B_prime);
+        let C_C_prime = gens.commit_prefix(&r_C, &w_C_prime);
 
         let w_1 = w[6];
         let w_2 = w[7];
@@ -114,11 +179,8 @@ where
 
         let cross_term_2: Vec<C::ScalarExt> = hadamard_prod::<C>(&r_A, &r_B);
 
-        let C_1 = self.comm_gens.commit(&cross_term_1, &w_1);
-        let C_2 = self.comm_gens.commit(&cross_term_2, &w_2);
-
-        self.transcript
-            .append_points(&[C_A, C_B, C_C, C_A_prime, C_B_prime, C_C_prime, C_1, C_2]);
+        let C_1 = gens.commit_prefix(&cross_term_1, &w_1);
+        let C_2 = gens.commit_prefix(&cross_term_2, &w_2);
 
         // Step 6
 
@@ -135,7 +197,7 @@ where
 
         // Step 7
 
-        let gamma = self.transcript.squeeze(1)[0];
+        let gamma = squeeze_gamma(transcript, &pi_1);
 
         // Step 8
 
@@ -169,10 +231,10 @@ where
 
         // Step 12
 
-        R1CSNARKProof {
+        Ok(R1CSNARKProof {
             public_input: public_input.to_vec(),
             pi_1,
             pi_2,
-        }
+        })
     }
 }
@@ -1,15 +1,44 @@
 use crate::CurveAffineExt;
+use digest::{ExtendableOutput, Input};
 use ff::{Field, PrimeField, PrimeFieldBits};
 use halo2curves::FieldExt;
+use sha3::Shake256;
+use std::fmt;
+use std::io::Read;
 use zeroize::DefaultIsZeroes;
 
-use crate::utils::hadamard_prod;
+use crate::utils::{hadamard_prod, par_map};
 
-#[derive(Clone)]
-pub struct Matrix<C>(Vec<(usize, usize, C::ScalarExt)>)
+/// An entry of a `Matrix` outside its dimensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatrixError {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "entry ({}, {}) out of range", self.row, self.col)
+    }
+}
+
+impl std::error::Error for MatrixError {}
+
+/// A sparse matrix in compressed sparse row form:
+/// the entries of row `i` are `col_idx[row_ptr[i]..row_ptr[i + 1]]` and the matching `values`,
+/// in increasing order of column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix<C>
 where
     C: CurveAffineExt,
-    C::ScalarExt: PrimeFieldBits;
+    C::ScalarExt: PrimeFieldBits,
+{
+    num_rows: usize,
+    num_cols: usize,
+    row_ptr: Vec<usize>,
+    col_idx: Vec<usize>,
+    values: Vec<C::ScalarExt>,
+}
 
 impl<C> Matrix<C>
 where
@@ -18,20 +47,134 @@ where
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
 {
-    pub fn mul_vector(&self, num_rows: usize, vec: &Vec<C::ScalarExt>) -> Vec<C::ScalarExt> {
-        let mut result = vec![C::ScalarExt::zero(); num_rows];
-        for i in 0..self.0.len() {
-            let row = self.0[i].0;
-            let col = self.0[i].1;
-            let val = self.0[i].2;
-            result[row] += val * vec[col];
+    /// A `num_rows` by `num_cols` matrix from its `(row, column, value)` entries, in any order.
+    /// Entries at the same position add up.
+    pub fn new(
+        num_rows: usize,
+        num_cols: usize,
+        mut entries: Vec<(usize, usize, C::ScalarExt)>,
+    ) -> Result<Self, MatrixError> {
+        if let Some((row, col, _)) = entries
+            .iter()
+            .find(|(row, col, _)| *row >= num_rows || *col >= num_cols)
+        {
+            return Err(MatrixError {
+                row: *row,
+                col: *col,
+            });
+        }
+
+        entries.sort_by_key(|(row, col, _)| (*row, *col));
+
+        let mut row_ptr = vec![0; num_rows + 1];
+        let mut col_idx = Vec::with_capacity(entries.len());
+        let mut values: Vec<C::ScalarExt> = Vec::with_capacity(entries.len());
+        let mut last = None;
+        for (row, col, val) in entries {
+            if last == Some((row, col)) {
+                *values.last_mut().unwrap() += val;
+                continue;
+            }
+            last = Some((row, col));
+            row_ptr[row + 1] += 1;
+            col_idx.push(col);
+            values.push(val);
+        }
+        for i in 0..num_rows {
+            row_ptr[i + 1] += row_ptr[i];
+        }
+
+        Ok(Self {
+            num_rows,
+            num_cols,
+            row_ptr,
+            col_idx,
+            values,
+        })
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn num_cols(&self) -> usize {
+        self.num_cols
+    }
+
+    /// The number of stored entries.
+    pub fn num_entries(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The `(row, column, value)` entries in row-major order.
+    pub fn entries(&self) -> impl Iterator<Item = (usize, usize, C::ScalarExt)> + '_ {
+        (0..self.num_rows).flat_map(move |row| {
+            let range = self.row_ptr[row]..self.row_ptr[row + 1];
+            self.col_idx[range.clone()]
+                .iter()
+                .zip(self.values[range].iter())
+                .map(move |(col, val)| (row, *col, *val))
+        })
+    }
+
+    pub fn transpose(&self) -> Self {
+        let entries = self
+            .entries()
+            .map(|(row, col, val)| (col, row, val))
+            .collect();
+        // The entries of a valid matrix stay in range once swapped
+        Self::new(self.num_cols, self.num_rows, entries).unwrap()
+    }
+
+    // The inner product of row `i` with `vec`
+    fn row_dot(&self, i: usize, vec: &[C::ScalarExt]) -> C::ScalarExt {
+        let range = self.row_ptr[i]..self.row_ptr[i + 1];
+        self.col_idx[range.clone()]
+            .iter()
+            .zip(self.values[range].iter())
+            .fold(C::ScalarExt::zero(), |acc, (col, val)| {
+                acc + *val * vec[*col]
+            })
+    }
+
+    // The inner products of row `i` with `z` and with `r`
+    fn row_dot_pair(
+        &self,
+        i: usize,
+        z: &[C::ScalarExt],
+        r: &[C::ScalarExt],
+    ) -> (C::ScalarExt, C::ScalarExt) {
+        let range = self.row_ptr[i]..self.row_ptr[i + 1];
+        self.col_idx[range.clone()]
+            .iter()
+            .zip(self.values[range].iter())
+            .fold(
+                (C::ScalarExt::zero(), C::ScalarExt::zero()),
+                |(mz, mr), (col, val)| (mz + *val * z[*col], mr + *val * r[*col]),
+            )
+    }
+
+    pub fn mul_vector(&self, vec: &[C::ScalarExt]) -> Vec<C::ScalarExt> {
+        assert_eq!(vec.len(), self.num_cols);
+        par_map(self.num_rows, |i| self.row_dot(i, vec))
+    }
+
+    fn absorb_into(&self, shake: &mut Shake256) {
+        shake.input((self.num_entries() as u64).to_le_bytes());
+        for (row, col, val) in self.entries() {
+            shake.input((row as u64).to_le_bytes());
+            shake.input((col as u64).to_le_bytes());
+            shake.input(val.to_repr());
         }
-        result
     }
 }
 
+/// The A, B, C matrices of a circuit and their dimensions, without an assignment.
+///
+/// The matrices act on `z = (1, x, w)`: the constant one in column 0, then the
+/// `num_input` public inputs, then the `num_vars` witness variables.
 #[derive(Clone)]
-pub struct R1CS<C>
+pub struct R1CSShape<C>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
@@ -41,21 +184,97 @@ where
     pub A: Matrix<C>,
     pub B: Matrix<C>,
     pub C: Matrix<C>,
-    pub witness: Vec<C::ScalarExt>,
-    pub public_input: Vec<C::ScalarExt>,
     pub num_cons: usize,
     pub num_vars: usize,
     pub num_input: usize,
+    /// Optional names of the constraints, for diagnostics. Either empty or one per constraint,
+    /// and not part of the digest.
+    pub constraint_labels: Vec<Option<String>>,
 }
 
-impl<C> R1CS<C>
+/// A constraint `(Az)_i * (Bz)_i = (Cz)_i` that an assignment does not satisfy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsatisfiedConstraint<F> {
+    pub index: usize,
+    pub Az: F,
+    pub Bz: F,
+    pub Cz: F,
+    pub label: Option<String>,
+}
+
+/// Why an assignment does not satisfy an `R1CSShape`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SatisfiabilityError<F> {
+    InvalidPublicInputLength {
+        expected: usize,
+        actual: usize,
+    },
+    InvalidWitnessLength {
+        expected: usize,
+        actual: usize,
+    },
+    /// The unsatisfied constraints, in increasing order of index.
+    Unsatisfied(Vec<UnsatisfiedConstraint<F>>),
+}
+
+impl<F> fmt::Display for SatisfiabilityError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPublicInputLength { expected, actual } => {
+                write!(
+                    f,
+                    "expected public input of length {}, got {}",
+                    expected, actual
+                )
+            }
+            Self::InvalidWitnessLength { expected, actual } => {
+                write!(f, "expected witness of length {}, got {}", expected, actual)
+            }
+            Self::Unsatisfied(constraints) => {
+                write!(f, "{} unsatisfied constraints", constraints.len())?;
+                if let Some(first) = constraints.first() {
+                    write!(f, ", the first at index {}", first.index)?;
+                    if let Some(label) = &first.label {
+                        write!(f, " ({})", label)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<F: fmt::Debug> std::error::Error for SatisfiabilityError<F> {}
+
+impl<C> R1CSShape<C>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
 {
-    pub fn produce_synthetic_r1cs(num_cons: usize, num_vars: usize, num_input: usize) -> Self {
+    /// Hash of the dimensions and the A, B, C matrices (entries in row-major order),
+    /// which the NARK transcript absorbs to bind proofs to this circuit.
+    pub fn digest(&self) -> C::ScalarExt {
+        let mut shake = Shake256::default();
+        shake.input(b"r1cs-nark-r1cs-digest");
+        shake.input((self.num_cons as u64).to_le_bytes());
+        shake.input((self.num_vars as u64).to_le_bytes());
+        shake.input((self.num_input as u64).to_le_bytes());
+        self.A.absorb_into(&mut shake);
+        self.B.absorb_into(&mut shake);
+        self.C.absorb_into(&mut shake);
+
+        let mut digest_bytes = [0u8; 64];
+        shake.xof_result().read_exact(&mut digest_bytes).unwrap();
+        C::ScalarExt::from_bytes_wide(&digest_bytes)
+    }
+
+    pub fn produce_synthetic_r1cs(
+        num_cons: usize,
+        num_vars: usize,
+        num_input: usize,
+    ) -> (Self, R1CSInstance<C>, R1CSWitness<C>) {
         //        assert_eq!(num_cons, num_vars);
         let mut public_input = Vec::with_capacity(num_input);
         let mut witness = Vec::with_capacity(num_vars);
@@ -68,7 +287,7 @@ where
             witness.push(C::ScalarExt::from((i + 1) as u64));
         }
 
-        let z: Vec<C::ScalarExt> = vec![public_input.clone(), witness.clone()].concat();
+        let z = z_vector::<C>(C::ScalarExt::one(), &public_input, &witness);
 
         let mut A: Vec<(usize, usize, C::ScalarExt)> = vec![];
         let mut B: Vec<(usize, usize, C::ScalarExt)> = vec![];
@@ -90,28 +309,201 @@ where
             C.push((i, C_col, (z[A_col] * z[B_col]) * z[C_col].invert().unwrap()));
         }
 
-        Self {
-            A: Matrix(A),
-            B: Matrix(B),
-            C: Matrix(C),
-            witness,
-            public_input,
+        let num_cols = 1 + num_input + num_vars;
+        let shape = Self {
+            A: Matrix::new(num_cons, num_cols, A).unwrap(),
+            B: Matrix::new(num_cons, num_cols, B).unwrap(),
+            C: Matrix::new(num_cons, num_cols, C).unwrap(),
             num_cons,
             num_vars,
             num_input,
+            constraint_labels: vec![],
+        };
+
+        (
+            shape,
+            R1CSInstance::new(public_input),
+            R1CSWitness::new(witness),
+        )
+    }
+
+    /// `(Az, Bz, Cz)`, computed in a single pass over the constraints.
+    pub fn multiply_vec(
+        &self,
+        z: &[C::ScalarExt],
+    ) -> (Vec<C::ScalarExt>, Vec<C::ScalarExt>, Vec<C::ScalarExt>) {
+        assert_eq!(z.len(), self.A.num_cols());
+
+        let rows = par_map(self.num_cons, |i| {
+            (
+                self.A.row_dot(i, z),
+                self.B.row_dot(i, z),
+                self.C.row_dot(i, z),
+            )
+        });
+
+        let mut Az = Vec::with_capacity(self.num_cons);
+        let mut Bz = Vec::with_capacity(self.num_cons);
+        let mut Cz = Vec::with_capacity(self.num_cons);
+        for (a, b, c) in rows {
+            Az.push(a);
+            Bz.push(b);
+            Cz.push(c);
         }
+
+        (Az, Bz, Cz)
     }
 
-    pub fn is_sat(&self, witness: &Vec<C::ScalarExt>, public_input: &Vec<C::ScalarExt>) -> bool {
-        let mut z = Vec::with_capacity(witness.len() + public_input.len() + 1);
-        z.extend(public_input);
-        z.extend(witness);
+    /// `(Az, Bz, Cz)` and `(Ar, Br, Cr)`, computed in a single pass over the constraints
+    /// so that each row of the matrices is read once for both vectors.
+    pub fn multiply_vec_pair(
+        &self,
+        z: &[C::ScalarExt],
+        r: &[C::ScalarExt],
+    ) -> (
+        (Vec<C::ScalarExt>, Vec<C::ScalarExt>, Vec<C::ScalarExt>),
+        (Vec<C::ScalarExt>, Vec<C::ScalarExt>, Vec<C::ScalarExt>),
+    ) {
+        assert_eq!(z.len(), self.A.num_cols());
+        assert_eq!(r.len(), self.A.num_cols());
+
+        let rows = par_map(self.num_cons, |i| {
+            (
+                self.A.row_dot_pair(i, z, r),
+                self.B.row_dot_pair(i, z, r),
+                self.C.row_dot_pair(i, z, r),
+            )
+        });
+
+        let mut Az = Vec::with_capacity(self.num_cons);
+        let mut Bz = Vec::with_capacity(self.num_cons);
+        let mut Cz = Vec::with_capacity(self.num_cons);
+        let mut Ar = Vec::with_capacity(self.num_cons);
+        let mut Br = Vec::with_capacity(self.num_cons);
+        let mut Cr = Vec::with_capacity(self.num_cons);
+        for ((az, ar), (bz, br), (cz, cr)) in rows {
+            Az.push(az);
+            Bz.push(bz);
+            Cz.push(cz);
+            Ar.push(ar);
+            Br.push(br);
+            Cr.push(cr);
+        }
+
+        ((Az, Bz, Cz), (Ar, Br, Cr))
+    }
+
+    pub fn is_sat(&self, instance: &R1CSInstance<C>, witness: &R1CSWitness<C>) -> bool {
+        self.check_sat(instance, witness).is_ok()
+    }
+
+    /// Like `is_sat`, but reports every constraint the assignment does not satisfy.
+    pub fn check_sat(
+        &self,
+        instance: &R1CSInstance<C>,
+        witness: &R1CSWitness<C>,
+    ) -> Result<(), SatisfiabilityError<C::ScalarExt>> {
+        if instance.public_input.len() != self.num_input {
+            return Err(SatisfiabilityError::InvalidPublicInputLength {
+                expected: self.num_input,
+                actual: instance.public_input.len(),
+            });
+        }
+        if witness.w.len() != self.num_vars {
+            return Err(SatisfiabilityError::InvalidWitnessLength {
+                expected: self.num_vars,
+                actual: witness.w.len(),
+            });
+        }
+
+        let z = z_vector::<C>(C::ScalarExt::one(), &instance.public_input, &witness.w);
+
+        let (Az, Bz, Cz) = self.multiply_vec(&z);
+
+        let AzBz = hadamard_prod::<C>(&Az, &Bz);
+        let unsatisfied = (0..self.num_cons)
+            .filter(|&i| AzBz[i] != Cz[i])
+            .map(|i| UnsatisfiedConstraint {
+                index: i,
+                Az: Az[i],
+                Bz: Bz[i],
+                Cz: Cz[i],
+                label: self.constraint_labels.get(i).cloned().flatten(),
+            })
+            .collect::<Vec<_>>();
+
+        if unsatisfied.is_empty() {
+            Ok(())
+        } else {
+            Err(SatisfiabilityError::Unsatisfied(unsatisfied))
+        }
+    }
+}
+
+/// `z = (u, x, w)`, where `u` is 1 for a statement and the folded constant for an accumulator.
+pub(crate) fn z_vector<C>(
+    u: C::ScalarExt,
+    x: &[C::ScalarExt],
+    w: &[C::ScalarExt],
+) -> Vec<C::ScalarExt>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    let mut z = Vec::with_capacity(1 + x.len() + w.len());
+    z.push(u);
+    z.extend_from_slice(x);
+    z.extend_from_slice(w);
+    z
+}
+
+/// The public input of a statement about an `R1CSShape`, without the constant one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct R1CSInstance<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub public_input: Vec<C::ScalarExt>,
+}
 
-        let Az = self.A.mul_vector(self.num_cons, &z);
-        let Bz = self.B.mul_vector(self.num_cons, &z);
-        let Cz = self.C.mul_vector(self.num_cons, &z);
+/// An assignment to the witness variables of an `R1CSShape`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct R1CSWitness<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub w: Vec<C::ScalarExt>,
+}
 
-        hadamard_prod::<C>(&Az, &Bz) == Cz
+impl<C> R1CSInstance<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub fn new(public_input: Vec<C::ScalarExt>) -> Self {
+        Self { public_input }
+    }
+}
+
+impl<C> R1CSWitness<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub fn new(w: Vec<C::ScalarExt>) -> Self {
+        Self { w }
     }
 }
 
@@ -127,11 +519,149 @@ mod tests {
         let num_input = 5;
         type C = Secp256k1Affine;
 
-        let r1cs = R1CS::<C>::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+        let (shape, instance, witness) =
+            R1CSShape::<C>::produce_synthetic_r1cs(num_cons, num_vars, num_input);
+
+        assert_eq!(witness.w.len(), num_vars);
+        assert_eq!(instance.public_input.len(), num_input);
+
+        assert!(shape.is_sat(&instance, &witness));
+
+        let mut bad_witness = witness.clone();
+        bad_witness.w[0] += <C as halo2curves::CurveAffine>::ScalarExt::one();
+        assert!(!shape.is_sat(&instance, &bad_witness));
+
+        let short_instance = R1CSInstance::new(instance.public_input[1..].to_vec());
+        assert!(!shape.is_sat(&short_instance, &witness));
+    }
+
+    #[test]
+    fn test_check_sat() {
+        type C = Secp256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        let (mut shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(20, 10, 5);
+        assert_eq!(shape.check_sat(&instance, &witness), Ok(()));
+
+        // w[0] is z[6], after the constant and the 5 public inputs
+        let mut bad_witness = witness.clone();
+        bad_witness.w[0] = F::from(100);
+        shape.constraint_labels = (0..20).map(|i| Some(format!("c{}", i))).collect();
+
+        let unsatisfied = match shape.check_sat(&instance, &bad_witness) {
+            Err(SatisfiabilityError::Unsatisfied(unsatisfied)) => unsatisfied,
+            other => panic!("expected unsatisfied constraints, got {:?}", other),
+        };
+        // Constraints 4, 14 use column 6 in C, 5, 15 in B and 6, 16 in A
+        assert_eq!(
+            unsatisfied.iter().map(|c| c.index).collect::<Vec<_>>(),
+            vec![4, 5, 6, 14, 15, 16]
+        );
+
+        let z = z_vector::<C>(F::one(), &instance.public_input, &bad_witness.w);
+        for c in &unsatisfied {
+            assert_eq!(c.label, Some(format!("c{}", c.index)));
+            assert_eq!(c.Az, z[c.index % 10]);
+            assert_eq!(c.Bz, z[(c.index + 1) % 10]);
+            assert!(c.Az * c.Bz != c.Cz);
+        }
+
+        assert_eq!(
+            shape.check_sat(&instance, &R1CSWitness::new(witness.w[1..].to_vec())),
+            Err(SatisfiabilityError::InvalidWitnessLength {
+                expected: 10,
+                actual: 9
+            })
+        );
+    }
+
+    #[test]
+    fn test_matrix() {
+        type C = Secp256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        // Out of order, with a repeated position
+        let M = Matrix::<C>::new(
+            2,
+            3,
+            vec![
+                (1, 2, F::from(5)),
+                (0, 1, F::from(2)),
+                (1, 0, F::from(3)),
+                (1, 2, F::from(1)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            M.entries().collect::<Vec<_>>(),
+            vec![(0, 1, F::from(2)), (1, 0, F::from(3)), (1, 2, F::from(6))]
+        );
+        assert_eq!(
+            M.mul_vector(&[F::from(1), F::from(10), F::from(100)]),
+            vec![F::from(20), F::from(603)]
+        );
+
+        let T = M.transpose();
+        assert_eq!((T.num_rows(), T.num_cols()), (3, 2));
+        assert_eq!(
+            T.mul_vector(&[F::from(1), F::from(10)]),
+            vec![F::from(30), F::from(2), F::from(60)]
+        );
+        assert_eq!(T.transpose(), M);
+
+        assert_eq!(
+            Matrix::<C>::new(2, 3, vec![(0, 3, F::one())]),
+            Err(MatrixError { row: 0, col: 3 })
+        );
+        assert_eq!(
+            Matrix::<C>::new(2, 3, vec![(2, 0, F::one())]),
+            Err(MatrixError { row: 2, col: 0 })
+        );
+    }
+
+    #[test]
+    fn test_multiply_vec() {
+        type C = Secp256k1Affine;
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(20, 10, 5);
+        let z = z_vector::<C>(F::one(), &instance.public_input, &witness.w);
+        let r = (0..z.len())
+            .map(|i| F::from(i as u64 + 7))
+            .collect::<Vec<_>>();
+
+        let separate = |v: &[F]| {
+            (
+                shape.A.mul_vector(v),
+                shape.B.mul_vector(v),
+                shape.C.mul_vector(v),
+            )
+        };
+        assert_eq!(shape.multiply_vec(&z), separate(&z));
+        assert_eq!(
+            shape.multiply_vec_pair(&z, &r),
+            (separate(&z), separate(&r))
+        );
+    }
+
+    #[test]
+    fn test_r1cs_digest() {
+        type C = Secp256k1Affine;
+
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(20, 10, 5);
+        assert_eq!(shape.digest(), shape.clone().digest());
+
+        // Swapping A and B keeps the circuit satisfiable but changes the digest
+        let mut swapped = shape.clone();
+        std::mem::swap(&mut swapped.A, &mut swapped.B);
+        assert!(swapped.is_sat(&instance, &witness));
+        assert!(swapped.digest() != shape.digest());
 
-        assert_eq!(r1cs.witness.len(), num_vars);
-        assert_eq!(r1cs.public_input.len(), num_input);
+        let mut scaled = shape.clone();
+        scaled.C.values[0] += <C as halo2curves::CurveAffine>::ScalarExt::one();
+        assert!(scaled.digest() != shape.digest());
 
-        assert!(r1cs.is_sat(&r1cs.witness, &r1cs.public_input));
+        let (other_dims, _, _) = R1CSShape::<C>::produce_synthetic_r1cs(20, 10, 6);
+        assert!(other_dims.digest() != shape.digest());
     }
 }
@@ -0,0 +1,327 @@
+//! Canonical byte encoding of R1CS NARK proofs.
+//!
+//! Version 1 layout of [`R1CSNARKProof::to_bytes`] (integers are little-endian):
+//!
+//! ```text
+//! version        u8                  = 1
+//! pi_1           8 compressed points  C_A, C_B, C_C, C_A', C_B', C_C', C_1, C_2
+//! pi_2.s         u32 length || length scalars
+//! pi_2.sigma_*   4 scalars           sigma_A, sigma_B, sigma_C, sigma_O
+//! public_input   u32 length || length scalars
+//! ```
+//!
+//! Points use the curve's compressed `GroupEncoding`, scalars their 32-byte canonical repr.
+//! `Pi1` and `Pi2` encode to the corresponding unversioned parts of this layout.
+
+use super::{Pi1, Pi2, R1CSNARKProof};
+use crate::CurveAffineExt;
+use ff::{PrimeField, PrimeFieldBits};
+use halo2curves::group::GroupEncoding;
+use std::fmt;
+use zeroize::DefaultIsZeroes;
+
+pub const PROOF_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodingError {
+    UnsupportedVersion(u8),
+    /// The input ended before the value being read.
+    UnexpectedEnd,
+    /// Bytes remain after the value was fully read.
+    TrailingBytes(usize),
+    NonCanonicalScalar,
+    /// The bytes do not encode a point on the curve.
+    InvalidPoint,
+}
+
+impl fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedVersion(v) => write!(f, "unsupported proof version {}", v),
+            Self::UnexpectedEnd => write!(f, "unexpected end of input"),
+            Self::TrailingBytes(n) => write!(f, "{} trailing bytes after proof", n),
+            Self::NonCanonicalScalar => write!(f, "non-canonical scalar encoding"),
+            Self::InvalidPoint => write!(f, "invalid curve point encoding"),
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn read(&mut self, n: usize) -> Result<&'a [u8], DecodingError> {
+        if self.bytes.len() < n {
+            return Err(DecodingError::UnexpectedEnd);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DecodingError> {
+        Ok(self.read(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DecodingError> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.read(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn read_scalar<F: PrimeField<Repr = [u8; 32]>>(&mut self) -> Result<F, DecodingError> {
+        let mut repr = [0u8; 32];
+        repr.copy_from_slice(self.read(32)?);
+        Option::from(F::from_repr(repr)).ok_or(DecodingError::NonCanonicalScalar)
+    }
+
+    fn read_scalars<F: PrimeField<Repr = [u8; 32]>>(&mut self) -> Result<Vec<F>, DecodingError> {
+        let len = self.read_u32()? as usize;
+        // Check the length against the remaining input before allocating
+        if self.bytes.len() / 32 < len {
+            return Err(DecodingError::UnexpectedEnd);
+        }
+        (0..len).map(|_| self.read_scalar()).collect()
+    }
+
+    fn read_point<C: GroupEncoding>(&mut self) -> Result<C, DecodingError> {
+        let mut repr = C::Repr::default();
+        let len = repr.as_ref().len();
+        repr.as_mut().copy_from_slice(self.read(len)?);
+        Option::from(C::from_bytes(&repr)).ok_or(DecodingError::InvalidPoint)
+    }
+
+    fn finish(self) -> Result<(), DecodingError> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(DecodingError::TrailingBytes(self.bytes.len()))
+        }
+    }
+}
+
+fn write_scalars<F: PrimeField<Repr = [u8; 32]>>(bytes: &mut Vec<u8>, scalars: &[F]) {
+    bytes.extend_from_slice(&(scalars.len() as u32).to_le_bytes());
+    for scalar in scalars {
+        bytes.extend_from_slice(&scalar.to_repr());
+    }
+}
+
+impl<C> Pi1<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    fn write(&self, bytes: &mut Vec<u8>) {
+        for point in self.points() {
+            bytes.extend_from_slice(point.to_bytes().as_ref());
+        }
+    }
+
+    fn read(reader: &mut ByteReader) -> Result<Self, DecodingError> {
+        Ok(Self {
+            C_A: reader.read_point()?,
+            C_B: reader.read_point()?,
+            C_C: reader.read_point()?,
+            C_A_prime: reader.read_point()?,
+            C_B_prime: reader.read_point()?,
+            C_C_prime: reader.read_point()?,
+            C_1: reader.read_point()?,
+            C_2: reader.read_point()?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write(&mut bytes);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodingError> {
+        let mut reader = ByteReader { bytes };
+        let pi_1 = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(pi_1)
+    }
+}
+
+impl<C> Pi2<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    fn write(&self, bytes: &mut Vec<u8>) {
+        write_scalars(bytes, &self.s);
+        for sigma in [self.sigma_A, self.sigma_B, self.sigma_C, self.sigma_O] {
+            bytes.extend_from_slice(&sigma.to_repr());
+        }
+    }
+
+    fn read(reader: &mut ByteReader) -> Result<Self, DecodingError> {
+        Ok(Self {
+            s: reader.read_scalars()?,
+            sigma_A: reader.read_scalar()?,
+            sigma_B: reader.read_scalar()?,
+            sigma_C: reader.read_scalar()?,
+            sigma_O: reader.read_scalar()?,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write(&mut bytes);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodingError> {
+        let mut reader = ByteReader { bytes };
+        let pi_2 = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(pi_2)
+    }
+}
+
+impl<C> R1CSNARKProof<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![PROOF_VERSION];
+        self.pi_1.write(&mut bytes);
+        self.pi_2.write(&mut bytes);
+        write_scalars(&mut bytes, &self.public_input);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodingError> {
+        let mut reader = ByteReader { bytes };
+
+        let version = reader.read_u8()?;
+        if version != PROOF_VERSION {
+            return Err(DecodingError::UnsupportedVersion(version));
+        }
+
+        let pi_1 = Pi1::read(&mut reader)?;
+        let pi_2 = Pi2::read(&mut reader)?;
+        let public_input = reader.read_scalars()?;
+        reader.finish()?;
+
+        Ok(Self {
+            public_input,
+            pi_1,
+            pi_2,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r1cs::{setup, R1CSInstance, R1CSNARKProver, R1CSShape, Verifier, VerifyingKey};
+    use halo2curves::secq256k1::Secq256k1Affine;
+    use poseidon_transcript::sponge::SpongeCurve;
+    use poseidon_transcript::transcript::PoseidonTranscript;
+
+    type C = Secq256k1Affine;
+
+    fn proof_and_r1cs() -> (R1CSNARKProof<C>, R1CSInstance<C>, VerifyingKey<C>) {
+        let (shape, instance, witness) = R1CSShape::<C>::produce_synthetic_r1cs(8, 8, 2);
+        let (pk, vk) = setup(&shape, b"test_serialization");
+
+        let transcript = PoseidonTranscript::new(b"test_serialization", SpongeCurve::K256);
+        let mut prover = R1CSNARKProver::new(pk, transcript);
+        let proof = prover.prove(&instance, &witness).unwrap();
+
+        (proof, instance, vk)
+    }
+
+    #[test]
+    fn test_proof_roundtrip() {
+        let (proof, instance, vk) = proof_and_r1cs();
+
+        let bytes = proof.to_bytes();
+        let decoded = R1CSNARKProof::<C>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        assert_eq!(decoded.to_bytes(), bytes);
+
+        assert_eq!(
+            Pi1::<C>::from_bytes(&proof.pi_1.to_bytes()).unwrap(),
+            proof.pi_1
+        );
+        assert_eq!(
+            Pi2::<C>::from_bytes(&proof.pi_2.to_bytes()).unwrap(),
+            proof.pi_2
+        );
+
+        let transcript = PoseidonTranscript::new(b"test_serialization", SpongeCurve::K256);
+        let verifier = Verifier::new(vk, transcript);
+        assert_eq!(verifier.verify(&decoded, &instance), Ok(()));
+    }
+
+    #[test]
+    fn test_reject_malformed_proof() {
+        let (proof, _, _) = proof_and_r1cs();
+        let bytes = proof.to_bytes();
+        let point_len = <<C as GroupEncoding>::Repr as Default>::default()
+            .as_ref()
+            .len();
+
+        let mut bad_version = bytes.clone();
+        bad_version[0] = PROOF_VERSION + 1;
+        assert_eq!(
+            R1CSNARKProof::<C>::from_bytes(&bad_version),
+            Err(DecodingError::UnsupportedVersion(PROOF_VERSION + 1))
+        );
+
+        assert_eq!(
+            R1CSNARKProof::<C>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodingError::UnexpectedEnd)
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            R1CSNARKProof::<C>::from_bytes(&trailing),
+            Err(DecodingError::TrailingBytes(1))
+        );
+
+        // Replace C_A with bytes that do not encode a curve point
+        let mut bad_point = bytes.clone();
+        for b in &mut bad_point[1..1 + point_len] {
+            *b = 0xff;
+        }
+        assert_eq!(
+            R1CSNARKProof::<C>::from_bytes(&bad_point),
+            Err(DecodingError::InvalidPoint)
+        );
+
+        // Replace s[0] with a value larger than the scalar modulus
+        let s_offset = 1 + 8 * point_len + 4;
+        let mut bad_scalar = bytes.clone();
+        for b in &mut bad_scalar[s_offset..s_offset + 32] {
+            *b = 0xff;
+        }
+        assert_eq!(
+            R1CSNARKProof::<C>::from_bytes(&bad_scalar),
+            Err(DecodingError::NonCanonicalScalar)
+        );
+
+        // A length prefix larger than the remaining input
+        let mut bad_length = bytes.clone();
+        bad_length[s_offset - 4..s_offset].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            R1CSNARKProof::<C>::from_bytes(&bad_length),
+            Err(DecodingError::UnexpectedEnd)
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use super::r1cs::R1CSShape;
+use crate::{CurveAffineExt, MultiCommitGens};
+use digest::{ExtendableOutput, Input};
+use ff::{PrimeField, PrimeFieldBits};
+use halo2curves::FieldExt;
+use sha3::Shake256;
+use std::io::Read;
+use zeroize::DefaultIsZeroes;
+
+/// What the prover needs to prove statements about a circuit.
+#[derive(Clone)]
+pub struct ProvingKey<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    shape: R1CSShape<C>,
+    gens: MultiCommitGens<C>,
+    digest: C::ScalarExt,
+}
+
+/// What the verifier needs to check proofs about a circuit. Holds no witness.
+#[derive(Clone)]
+pub struct VerifyingKey<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    shape: R1CSShape<C>,
+    gens: MultiCommitGens<C>,
+    digest: C::ScalarExt,
+}
+
+/// Preprocess a circuit into its proving and verifying keys.
+///
+/// The commitment key is derived from `label` and sized to commit to vectors of
+/// `num_cons` entries (the A, B, C products) and of `num_input` entries (the public input).
+/// Whichever is shorter is committed to under the first generators of the key.
+pub fn setup<C>(shape: &R1CSShape<C>, label: &[u8]) -> (ProvingKey<C>, VerifyingKey<C>)
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    let gens = MultiCommitGens::new(shape.num_cons.max(shape.num_input), label);
+    let digest = circuit_digest(shape, &gens);
+
+    let pk = ProvingKey {
+        shape: shape.clone(),
+        gens: gens.clone(),
+        digest,
+    };
+    let vk = VerifyingKey {
+        shape: shape.clone(),
+        gens,
+        digest,
+    };
+
+    (pk, vk)
+}
+
+// Digest of the circuit together with its commitment key,
+// which the NARK transcript absorbs before any prover message.
+fn circuit_digest<C>(shape: &R1CSShape<C>, gens: &MultiCommitGens<C>) -> C::ScalarExt
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    let mut shake = Shake256::default();
+    shake.input(b"r1cs-nark-circuit-digest");
+    shake.input(shape.digest().to_repr());
+    shake.input(gens.digest().to_repr());
+
+    let mut digest_bytes = [0u8; 64];
+    shake.xof_result().read_exact(&mut digest_bytes).unwrap();
+    C::ScalarExt::from_bytes_wide(&digest_bytes)
+}
+
+impl<C> ProvingKey<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    pub fn shape(&self) -> &R1CSShape<C> {
+        &self.shape
+    }
+
+    pub fn gens(&self) -> &MultiCommitGens<C> {
+        &self.gens
+    }
+
+    pub fn digest(&self) -> &C::ScalarExt {
+        &self.digest
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey<C> {
+        VerifyingKey {
+            shape: self.shape.clone(),
+            gens: self.gens.clone(),
+            digest: self.digest,
+        }
+    }
+}
+
+impl<C> VerifyingKey<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    /// Rebuild a verifying key for `shape` from a commitment key received from elsewhere,
+    /// after checking that the key was derived from `label` and fits the circuit.
+    pub fn from_gens(shape: &R1CSShape<C>, gens: MultiCommitGens<C>, label: &[u8]) -> Option<Self> {
+        if gens.G.len() != shape.num_cons.max(shape.num_input) || !gens.verify_derivation(label) {
+            return None;
+        }
+
+        let digest = circuit_digest(shape, &gens);
+        Some(Self {
+            shape: shape.clone(),
+            gens,
+            digest,
+        })
+    }
+
+    pub fn shape(&self) -> &R1CSShape<C> {
+        &self.shape
+    }
+
+    pub fn gens(&self) -> &MultiCommitGens<C> {
+        &self.gens
+    }
+
+    pub fn digest(&self) -> &C::ScalarExt {
+        &self.digest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves::secq256k1::Secq256k1Affine;
+
+    #[test]
+    fn test_setup() {
+        type C = Secq256k1Affine;
+
+        let (shape, _, _) = R1CSShape::<C>::produce_synthetic_r1cs(4, 8, 6);
+        let (pk, vk) = setup(&shape, b"test_setup");
+
+        // Large enough to commit to the public input as well as the constraints
+        assert_eq!(pk.gens().G.len(), 6);
+        assert_eq!(pk.digest(), vk.digest());
+        assert!(vk.gens().verify_derivation(b"test_setup"));
+
+        let (_, other_vk) = setup(&shape, b"another_label");
+        assert!(other_vk.digest() != vk.digest());
+
+        let rebuilt = VerifyingKey::from_gens(&shape, vk.gens().clone(), b"test_setup").unwrap();
+        assert_eq!(rebuilt.digest(), vk.digest());
+        assert!(VerifyingKey::from_gens(&shape, vk.gens().clone(), b"another_label").is_none());
+        assert!(VerifyingKey::from_gens(
+            &shape,
+            MultiCommitGens::new(4, b"test_setup"),
+            b"test_setup"
+        )
+        .is_none());
+    }
+}
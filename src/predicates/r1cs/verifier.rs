@@ -1,13 +1,90 @@
-use super::R1CS;
-use crate::CurveAffineExt;
-use crate::{r1cs::R1CSNARKProof, utils::hadamard_prod, MultiCommitGens};
-use ff::{PrimeField, PrimeFieldBits};
-use halo2curves::group::Curve;
+use super::r1cs::{z_vector, R1CSInstance};
+use super::setup::VerifyingKey;
+use crate::commitment::msm;
+use crate::r1cs::{squeeze_gamma, statement_transcript, R1CSNARKProof};
+use crate::utils::{hadamard_prod, par_map};
+use crate::{CurveAffineExt, MultiCommitGens, Transcript, PRNG};
+use ff::{Field, PrimeField, PrimeFieldBits};
+use halo2curves::group::Group;
 use halo2curves::FieldExt;
 pub use poseidon_transcript::transcript::PoseidonTranscript;
+use std::fmt;
 use zeroize::DefaultIsZeroes;
 
-pub struct Verifier<C>
+/// The check of the R1CS NARK verifier that a proof failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationError {
+    /// `s` does not have one entry per witness variable.
+    InvalidSLength { expected: usize, actual: usize },
+    /// The public input does not have `num_input` entries.
+    InvalidPublicInputLength { expected: usize, actual: usize },
+    /// The proof carries another public input than the instance.
+    PublicInputMismatch,
+    /// cm(A(x, s); sigma_A) != C_A + gamma * C_A'
+    CommSAMismatch,
+    /// cm(B(x, s); sigma_B) != C_B + gamma * C_B'
+    CommSBMismatch,
+    /// cm(C(x, s); sigma_C) != C_C + gamma * C_C'
+    CommSCMismatch,
+    /// cm(A(x, s) o B(x, s); sigma_O) != C_C + gamma * C_1 + gamma^2 * C_2
+    HadamardMismatch,
+    /// The random combination of the commitment equations does not hold,
+    /// although each of them holds on its own.
+    CombinedMismatch,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSLength { expected, actual } => {
+                write!(f, "expected s of length {}, got {}", expected, actual)
+            }
+            Self::InvalidPublicInputLength { expected, actual } => {
+                write!(
+                    f,
+                    "expected public input of length {}, got {}",
+                    expected, actual
+                )
+            }
+            Self::PublicInputMismatch => {
+                write!(f, "public input of the proof does not match the instance")
+            }
+            Self::CommSAMismatch => write!(f, "commitment to A(x, s) does not match"),
+            Self::CommSBMismatch => write!(f, "commitment to B(x, s) does not match"),
+            Self::CommSCMismatch => write!(f, "commitment to C(x, s) does not match"),
+            Self::HadamardMismatch => {
+                write!(
+                    f,
+                    "commitment to A(x, s) o B(x, s) does not match the cross terms"
+                )
+            }
+            Self::CombinedMismatch => write!(f, "combined commitment equations do not hold"),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// The first proof of a batch that failed verification, by its index in the batch.
+/// The index is `None` if only the combined check of the whole batch failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchVerificationError {
+    pub index: Option<usize>,
+    pub error: VerificationError,
+}
+
+impl fmt::Display for BatchVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "proof {} of the batch: {}", index, self.error),
+            None => write!(f, "batch: {}", self.error),
+        }
+    }
+}
+
+impl std::error::Error for BatchVerificationError {}
+
+pub struct Verifier<C, T = PoseidonTranscript<C>>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
@@ -15,96 +92,323 @@ where
     C::ScalarExt: DefaultIsZeroes,
     C::Base: FieldExt<Repr = [u8; 32]>,
 {
-    transcript: PoseidonTranscript<C>,
-    pub r1cs: R1CS<C>,
-    pub comm_gens: MultiCommitGens<C>,
+    // The base transcript, forked for each proof as by the prover
+    transcript: T,
+    pub vk: VerifyingKey<C>,
 }
 
-impl<C> Verifier<C>
+impl<C, T> Verifier<C, T>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
     C::Base: FieldExt<Repr = [u8; 32]>,
+    T: Transcript<C>,
 {
-    pub fn new(
-        r1cs: R1CS<C>,
-        transcript: PoseidonTranscript<C>,
-        comm_gens: MultiCommitGens<C>,
-    ) -> Self {
-        Self {
-            r1cs,
-            transcript,
-            comm_gens,
+    pub fn new(vk: VerifyingKey<C>, transcript: T) -> Self {
+        Self { vk, transcript }
+    }
+
+    pub fn verify(
+        &self,
+        proof: &R1CSNARKProof<C>,
+        instance: &R1CSInstance<C>,
+    ) -> Result<(), VerificationError> {
+        let (gamma, s_A, s_B, s_C) = open_products(&self.vk, &self.transcript, proof, instance)?;
+
+        check_commitments(self.vk.gens(), proof, gamma, &s_A, &s_B, &s_C)
+    }
+
+    /// The challenge gamma that `verify` derives for `proof`.
+    pub fn challenge(&self, proof: &R1CSNARKProof<C>, instance: &R1CSInstance<C>) -> C::ScalarExt {
+        let transcript =
+            statement_transcript(&self.transcript, self.vk.digest(), &instance.public_input);
+        squeeze_gamma(transcript, &proof.pi_1)
+    }
+
+    /// Like `verify`, but checks the four commitment equations of steps 4 and 5 at once,
+    /// combined with random weights into a single multiexp over the commitment key and
+    /// the points of `Pi1`.
+    ///
+    /// A proof that fails any of the equations passes the combined check only if the weights
+    /// happen to cancel the failure, which has probability at most 1/p for a scalar field of
+    /// order p. A proof that `verify` rejects gets the same error as from `verify`.
+    /// If the combined check fails while each equation holds, which the weights cannot
+    /// cause, the proof is rejected with `CombinedMismatch`.
+    pub fn verify_combined(
+        &self,
+        proof: &R1CSNARKProof<C>,
+        instance: &R1CSInstance<C>,
+    ) -> Result<(), VerificationError> {
+        let (gamma, s_A, s_B, s_C) = open_products(&self.vk, &self.transcript, proof, instance)?;
+
+        let gens = self.vk.gens();
+        let mut equation = CombinedEquation::new(s_A.len());
+        let weights = PRNG::<C, T>::new(&self.transcript).squeeze(4);
+        equation.add(proof, gamma, &s_A, &s_B, &s_C, &weights);
+
+        if equation.holds(gens) {
+            Ok(())
+        } else {
+            // Find out which of the equations failed
+            check_commitments(gens, proof, gamma, &s_A, &s_B, &s_C)?;
+            Err(VerificationError::CombinedMismatch)
         }
     }
 
-    pub fn verify(&mut self, proof: &R1CSNARKProof<C>, public_input: &Vec<C::ScalarExt>) {
-        // Verify following the steps described in Section 8.1 of
+    /// Verify many proofs about the circuit of the verifying key, each with its own
+    /// public input.
+    ///
+    /// The commitment equations of all proofs are combined with random weights into a
+    /// single multiexp, as in `verify_combined`. If it fails, the proofs are verified
+    /// one by one to find the first bad one, and if there is none, the whole batch is
+    /// rejected with `CombinedMismatch`.
+    pub fn verify_batch(
+        &self,
+        proofs: &[(&R1CSNARKProof<C>, &R1CSInstance<C>)],
+    ) -> Result<(), BatchVerificationError> {
+        let gens = self.vk.gens();
+        let mut equation = CombinedEquation::new(self.vk.shape().num_cons);
+        let mut prng = PRNG::<C, T>::new(&self.transcript);
 
-        let num_cons = self.r1cs.num_cons;
-        let num_vars = self.r1cs.num_vars;
+        for (index, (proof, instance)) in proofs.iter().enumerate() {
+            let (gamma, s_A, s_B, s_C) = open_products(&self.vk, &self.transcript, proof, instance)
+                .map_err(|error| BatchVerificationError {
+                    index: Some(index),
+                    error,
+                })?;
 
-        // Step 1
+            let weights = prng.squeeze(4);
+            equation.add(proof, gamma, &s_A, &s_B, &s_C, &weights);
+        }
 
-        let pi_1 = &proof.pi_1;
-        let pi_2 = &proof.pi_2;
+        if equation.holds(gens) {
+            return Ok(());
+        }
+
+        for (index, (proof, instance)) in proofs.iter().enumerate() {
+            let (gamma, s_A, s_B, s_C) = open_products(&self.vk, &self.transcript, proof, instance)
+                .map_err(|error| BatchVerificationError {
+                    index: Some(index),
+                    error,
+                })?;
+            check_commitments(gens, proof, gamma, &s_A, &s_B, &s_C).map_err(|error| {
+                BatchVerificationError {
+                    index: Some(index),
+                    error,
+                }
+            })?;
+        }
+
+        Err(BatchVerificationError {
+            index: None,
+            error: VerificationError::CombinedMismatch,
+        })
+    }
+}
+
+// Steps 1 to 3: check the lengths, derive gamma from the transcript and compute
+// A(x, s), B(x, s), C(x, s).
+fn open_products<C, T>(
+    vk: &VerifyingKey<C>,
+    transcript: &T,
+    proof: &R1CSNARKProof<C>,
+    instance: &R1CSInstance<C>,
+) -> Result<
+    (
+        C::ScalarExt,
+        Vec<C::ScalarExt>,
+        Vec<C::ScalarExt>,
+        Vec<C::ScalarExt>,
+    ),
+    VerificationError,
+>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    C::Base: FieldExt<Repr = [u8; 32]>,
+    T: Transcript<C>,
+{
+    let public_input = &instance.public_input;
+
+    // Verify following the steps described in Section 8.1 of
+
+    let shape = vk.shape();
+    let num_vars = shape.num_vars;
+    let num_input = shape.num_input;
+
+    // Step 1
 
-        // Step 2
+    let pi_1 = &proof.pi_1;
+    let pi_2 = &proof.pi_2;
 
-        self.transcript.append_points(&[
-            pi_1.C_A,
-            pi_1.C_B,
-            pi_1.C_C,
-            pi_1.C_A_prime,
-            pi_1.C_B_prime,
-            pi_1.C_C_prime,
-            pi_1.C_1,
-            pi_1.C_2,
-        ]);
+    if pi_2.s.len() != num_vars {
+        return Err(VerificationError::InvalidSLength {
+            expected: num_vars,
+            actual: pi_2.s.len(),
+        });
+    }
+
+    if public_input.len() != num_input {
+        return Err(VerificationError::InvalidPublicInputLength {
+            expected: num_input,
+            actual: public_input.len(),
+        });
+    }
+
+    if proof.public_input != *public_input {
+        return Err(VerificationError::PublicInputMismatch);
+    }
+
+    // Step 2
+
+    let gamma = squeeze_gamma(
+        statement_transcript(transcript, vk.digest(), public_input),
+        pi_1,
+    );
+
+    // Step 3
+
+    let s_with_pub_input = z_vector::<C>(C::ScalarExt::one(), public_input, &pi_2.s);
+
+    let (s_A, s_B, s_C) = shape.multiply_vec(&s_with_pub_input);
+
+    Ok((gamma, s_A, s_B, s_C))
+}
 
-        let gamma: C::ScalarExt = self.transcript.squeeze(1)[0];
+// Steps 4 and 5, one equation at a time.
+fn check_commitments<C>(
+    gens: &MultiCommitGens<C>,
+    proof: &R1CSNARKProof<C>,
+    gamma: C::ScalarExt,
+    s_A: &[C::ScalarExt],
+    s_B: &[C::ScalarExt],
+    s_C: &[C::ScalarExt],
+) -> Result<(), VerificationError>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    let pi_1 = &proof.pi_1;
+    let pi_2 = &proof.pi_2;
+
+    // Step 4
 
-        // Step 3
+    let comm_s_A: C::Curve = gens.commit_prefix(s_A, &pi_2.sigma_A).into();
+    let comm_s_B: C::Curve = gens.commit_prefix(s_B, &pi_2.sigma_B).into();
+    let comm_s_C: C::Curve = gens.commit_prefix(s_C, &pi_2.sigma_C).into();
 
-        let mut s_with_pub_input = Vec::with_capacity(num_vars + public_input.len());
-        s_with_pub_input.extend_from_slice(&public_input);
-        s_with_pub_input.extend_from_slice(&pi_2.s);
+    if comm_s_A != pi_1.C_A + (pi_1.C_A_prime * gamma).into() {
+        return Err(VerificationError::CommSAMismatch);
+    }
+    if comm_s_B != pi_1.C_B + (pi_1.C_B_prime * gamma).into() {
+        return Err(VerificationError::CommSBMismatch);
+    }
+    if comm_s_C != pi_1.C_C + (pi_1.C_C_prime * gamma).into() {
+        return Err(VerificationError::CommSCMismatch);
+    }
 
-        assert_eq!(s_with_pub_input.len(), num_vars + public_input.len());
+    // Step 5
 
-        let s_A = self.r1cs.A.mul_vector(num_cons, &s_with_pub_input);
-        let s_B = self.r1cs.B.mul_vector(num_cons, &s_with_pub_input);
-        let s_C = self.r1cs.C.mul_vector(num_cons, &s_with_pub_input);
+    let comm_s_A_s_B: C::Curve = gens
+        .commit_prefix(&hadamard_prod::<C>(s_A, s_B), &pi_2.sigma_O)
+        .into();
 
-        // Step 4
+    let C_C: C::Curve = pi_1.C_C.into();
+    let C_1_gamma: C::Curve = (pi_1.C_1 * gamma).into();
+    let C_2_gamma_squared: C::Curve = (pi_1.C_2 * gamma * gamma).into();
 
-        let comm_s_A: C::Curve = self.comm_gens.commit(&s_A, &pi_2.sigma_A).into();
-        let comm_s_B: C::Curve = self.comm_gens.commit(&s_B, &pi_2.sigma_B).into();
-        let comm_s_C: C::Curve = self.comm_gens.commit(&s_C, &pi_2.sigma_C).into();
+    if comm_s_A_s_B != C_C + C_1_gamma + C_2_gamma_squared {
+        return Err(VerificationError::HadamardMismatch);
+    }
 
-        assert_eq!(comm_s_A, pi_1.C_A + (pi_1.C_A_prime * gamma).into());
-        assert_eq!(comm_s_B, pi_1.C_B + (pi_1.C_B_prime * gamma).into());
-        assert_eq!(comm_s_C, pi_1.C_C + (pi_1.C_C_prime * gamma).into());
+    Ok(())
+}
 
-        // Step 5
+// A weighted sum of commitment equations, kept as the scalars of one multiexp:
+// sum g_i * G_i + h * H + sum c_j * P_j, which is the identity if every equation holds.
+struct CombinedEquation<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    g: Vec<C::ScalarExt>,
+    h: C::ScalarExt,
+    points: Vec<(C::ScalarExt, C::Curve)>,
+}
 
-        let comm_s_A_s_B: C::Curve = self
-            .comm_gens
-            .commit(&hadamard_prod::<C>(&s_A, &s_B), &pi_2.sigma_O)
-            .into();
+impl<C> CombinedEquation<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    fn new(num_gens: usize) -> Self {
+        Self {
+            g: vec![C::ScalarExt::zero(); num_gens],
+            h: C::ScalarExt::zero(),
+            points: vec![],
+        }
+    }
 
-        println!("comm_s_A_s_B: {:?}", comm_s_A_s_B.to_affine());
+    // Add the four equations of steps 4 and 5 of a proof with the weights rho_1..rho_4:
+    // rho_1 * (cm(s_A; sigma_A) - C_A - gamma * C_A') + ...
+    // + rho_4 * (cm(s_A o s_B; sigma_O) - C_C - gamma * C_1 - gamma^2 * C_2)
+    fn add(
+        &mut self,
+        proof: &R1CSNARKProof<C>,
+        gamma: C::ScalarExt,
+        s_A: &[C::ScalarExt],
+        s_B: &[C::ScalarExt],
+        s_C: &[C::ScalarExt],
+        weights: &[C::ScalarExt],
+    ) {
+        let pi_1 = &proof.pi_1;
+        let pi_2 = &proof.pi_2;
+        let (rho_1, rho_2, rho_3, rho_4) = (weights[0], weights[1], weights[2], weights[3]);
 
-        let C_C: C::Curve = pi_1.C_C.into();
-        let C_1_gamma: C::Curve = (pi_1.C_1 * gamma).into();
-        let C_2_gamma_squared: C::Curve = (pi_1.C_2 * gamma * gamma).into();
+        let g = &self.g;
+        self.g = par_map(g.len(), |i| {
+            g[i] + rho_1 * s_A[i] + rho_2 * s_B[i] + rho_3 * s_C[i] + rho_4 * s_A[i] * s_B[i]
+        });
+        self.h += rho_1 * pi_2.sigma_A
+            + rho_2 * pi_2.sigma_B
+            + rho_3 * pi_2.sigma_C
+            + rho_4 * pi_2.sigma_O;
 
-        let lhs = C_C + C_1_gamma + C_2_gamma_squared;
+        let gamma_squared = gamma * gamma;
+        for (coeff, point) in [
+            (rho_1, pi_1.C_A),
+            (rho_1 * gamma, pi_1.C_A_prime),
+            (rho_2, pi_1.C_B),
+            (rho_2 * gamma, pi_1.C_B_prime),
+            (rho_3 + rho_4, pi_1.C_C),
+            (rho_3 * gamma, pi_1.C_C_prime),
+            (rho_4 * gamma, pi_1.C_1),
+            (rho_4 * gamma_squared, pi_1.C_2),
+        ] {
+            self.points.push((-coeff, point.into()));
+        }
+    }
 
-        println!("lhs: {:?}", lhs.to_affine());
+    fn holds(&self, gens: &MultiCommitGens<C>) -> bool {
+        let mut pairs = self
+            .g
+            .iter()
+            .zip(gens.G.iter())
+            .map(|(g_i, G_i)| (*g_i, (*G_i).into()))
+            .collect::<Vec<(C::ScalarExt, C::Curve)>>();
+        pairs.push((self.h, gens.h.into()));
+        pairs.extend_from_slice(&self.points);
 
-        assert_eq!(comm_s_A_s_B, C_C + C_1_gamma + C_2_gamma_squared);
+        bool::from(msm::<C>(&pairs).is_identity())
     }
 }
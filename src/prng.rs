@@ -1,39 +1,194 @@
-use crate::CurveAffineExt;
+use crate::{CurveAffineExt, Transcript};
+use digest::{ExtendableOutput, Input};
 use ff::{PrimeField, PrimeFieldBits};
-use poseidon_transcript::sponge::{PoseidonSponge, SpongeCurve};
-use rand_core::{OsRng, RngCore};
+use poseidon_transcript::transcript::PoseidonTranscript;
+use rand_core::{CryptoRng, OsRng, RngCore};
+use sha3::Shake256;
+use std::io::Read;
+use std::marker::PhantomData;
 use zeroize::DefaultIsZeroes;
 
-// Pseudo-random number generator
-pub struct PRNG<C>
+// Pseudo-random number generator, run on a private fork of a transcript.
+// Its output is scalars of C with the transcript's hash, whichever sponge or curve that uses.
+pub struct PRNG<C, T = PoseidonTranscript<C>>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
 {
-    sponge: PoseidonSponge<C::ScalarExt>,
+    transcript: T,
+    _marker: PhantomData<C>,
 }
 
-impl<C> PRNG<C>
+impl<C, T> PRNG<C, T>
 where
     C: CurveAffineExt,
     C::ScalarExt: PrimeFieldBits,
     C::ScalarExt: PrimeField<Repr = [u8; 32]>,
     C::ScalarExt: DefaultIsZeroes,
+    T: Transcript<C>,
 {
-    pub fn new() -> Self {
-        let mut sponge = PoseidonSponge::construct(b"r1cs-nark-prng", SpongeCurve::K256, None);
-        let mut rng = OsRng;
-        let mut bytes = [0u8; 32];
+    /// Seeded from the operating system's randomness, on a fork of `base`.
+    pub fn new(base: &T) -> Self {
+        Self::from_rng(base, &mut OsRng)
+    }
+
+    /// Seeded from 64 bytes drawn from `rng`, on a fork of `base`.
+    /// Every output of `rng` is a valid seed.
+    pub fn from_rng<R: RngCore + CryptoRng>(base: &T, rng: &mut R) -> Self {
+        let mut bytes = [0u8; 64];
         rng.fill_bytes(&mut bytes);
-        let random_scalar = C::ScalarExt::from_repr_vartime(bytes).unwrap();
-        sponge.absorb(&[random_scalar]);
+        Self::from_seed_bytes(base, &bytes)
+    }
+
+    /// A generator whose output is fixed by `base` and `seed`, to reproduce proofs in tests.
+    ///
+    /// Not for production: anyone who knows the seed learns the prover's blinders,
+    /// and with them the witness.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn from_seed_insecure(base: &T, seed: [u8; 32]) -> Self {
+        Self::from_seed_bytes(base, &seed)
+    }
+
+    fn from_seed_bytes(base: &T, bytes: &[u8]) -> Self {
+        let mut transcript = base.fork(b"r1cs-nark-prng");
+        transcript.append_bytes(bytes);
+
+        Self {
+            transcript,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Mix fresh randomness from `rng`, a hash of the `secrets` and `context` into the state.
+    ///
+    /// The output then stays unpredictable to anyone who does not know the secrets,
+    /// even if the seed and `rng` are both bad.
+    pub fn hedge<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        secrets: &[&[C::ScalarExt]],
+        context: &C::ScalarExt,
+    ) {
+        let mut fresh_bytes = [0u8; 64];
+        rng.fill_bytes(&mut fresh_bytes);
+
+        let mut shake = Shake256::default();
+        shake.input(b"r1cs-nark-prng-hedge");
+        for values in secrets {
+            shake.input((values.len() as u64).to_le_bytes());
+            for value in values.iter() {
+                shake.input(value.to_repr());
+            }
+        }
+        let mut secret_bytes = [0u8; 64];
+        shake.xof_result().read_exact(&mut secret_bytes).unwrap();
 
-        Self { sponge }
+        self.transcript.append_bytes(&fresh_bytes);
+        self.transcript.append_bytes(&secret_bytes);
+        self.transcript.append_scalar(context);
     }
 
     pub fn squeeze(&mut self, length: usize) -> Vec<C::ScalarExt> {
-        self.sponge.squeeze(length)
+        (0..length)
+            .map(|_| self.transcript.squeeze_challenge())
+            .collect()
+    }
+}
+
+// Yields all-0xff bytes, which are above the scalar modulus.
+// A fixed stand-in for the OS randomness in tests.
+#[cfg(test)]
+pub(crate) struct MaxRng;
+
+#[cfg(test)]
+impl RngCore for MaxRng {
+    fn next_u32(&mut self) -> u32 {
+        u32::MAX
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        u64::MAX
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.fill(0xff);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl CryptoRng for MaxRng {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Sha3Transcript;
+    use halo2curves::secq256k1::Secq256k1Affine;
+    use poseidon_transcript::sponge::SpongeCurve;
+
+    type C = Secq256k1Affine;
+
+    fn base() -> PoseidonTranscript<C> {
+        PoseidonTranscript::new(b"test-prng", SpongeCurve::K256)
+    }
+
+    #[test]
+    fn test_prng_seeding() {
+        // Bytes above the scalar modulus are a valid seed
+        let from_rng = PRNG::<C>::from_rng(&base(), &mut MaxRng).squeeze(4);
+        assert_eq!(
+            from_rng,
+            PRNG::<C>::from_rng(&base(), &mut MaxRng).squeeze(4)
+        );
+
+        let seeded = PRNG::<C>::from_seed_insecure(&base(), [7; 32]).squeeze(4);
+        assert_eq!(
+            seeded,
+            PRNG::<C>::from_seed_insecure(&base(), [7; 32]).squeeze(4)
+        );
+        assert!(seeded != PRNG::<C>::from_seed_insecure(&base(), [8; 32]).squeeze(4));
+        assert!(seeded != from_rng);
+
+        // The base transcript separates generators with the same seed
+        let other_base = PoseidonTranscript::new(b"test-prng-other", SpongeCurve::K256);
+        assert!(seeded != PRNG::<C>::from_seed_insecure(&other_base, [7; 32]).squeeze(4));
+
+        assert!(PRNG::<C>::new(&base()).squeeze(4) != PRNG::<C>::new(&base()).squeeze(4));
+    }
+
+    #[test]
+    fn test_prng_hedge() {
+        type F = <C as halo2curves::CurveAffine>::ScalarExt;
+
+        let secret = [F::from(3), F::from(4)];
+        let hedged = |secret: &[F], context: u64| {
+            let mut prng = PRNG::<C>::from_seed_insecure(&base(), [7; 32]);
+            prng.hedge(&mut MaxRng, &[secret], &F::from(context));
+            prng.squeeze(4)
+        };
+
+        // With a fixed seed and rng, the output still depends on the secret and the context
+        assert_eq!(hedged(&secret, 1), hedged(&secret, 1));
+        assert!(hedged(&secret, 1) != hedged(&secret[..1], 1));
+        assert!(hedged(&secret, 1) != hedged(&secret, 2));
+        assert!(hedged(&secret, 1) != PRNG::<C>::from_seed_insecure(&base(), [7; 32]).squeeze(4));
+    }
+
+    #[test]
+    fn test_prng_sha3_transcript() {
+        let sha3_base = Sha3Transcript::<C>::new(b"test-prng");
+
+        let seeded = PRNG::<C, _>::from_seed_insecure(&sha3_base, [7; 32]).squeeze(4);
+        assert_eq!(
+            seeded,
+            PRNG::<C, _>::from_seed_insecure(&sha3_base, [7; 32]).squeeze(4)
+        );
+        assert!(seeded != PRNG::<C>::from_seed_insecure(&base(), [7; 32]).squeeze(4));
     }
 }
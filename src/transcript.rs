@@ -0,0 +1,223 @@
+//! Fiat-Shamir transcripts.
+//!
+//! The provers and verifiers are generic over [`Transcript`], so that the challenges can be
+//! derived with a hash the eventual verifier has at hand: Poseidon for a verifier in a circuit,
+//! or SHA3 / Keccak for one without a Poseidon implementation, such as the EVM.
+//! A prover and its verifier must use the same transcript type with the same label.
+
+use crate::CurveAffineExt;
+use digest::Digest;
+use ff::{PrimeField, PrimeFieldBits};
+use halo2curves::group::GroupEncoding;
+use halo2curves::FieldExt;
+use poseidon_transcript::transcript::PoseidonTranscript;
+use sha3::{Keccak256, Sha3_256};
+use std::marker::PhantomData;
+use zeroize::DefaultIsZeroes;
+
+pub trait Transcript<C>: Clone
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+{
+    fn append_bytes(&mut self, bytes: &[u8]);
+
+    fn append_scalar(&mut self, scalar: &C::ScalarExt);
+
+    fn append_point(&mut self, point: &C);
+
+    fn append_points(&mut self, points: &[C]) {
+        for point in points {
+            self.append_point(point);
+        }
+    }
+
+    /// A challenge derived from everything appended so far, which is itself absorbed
+    /// so that the next challenge differs.
+    fn squeeze_challenge(&mut self) -> C::ScalarExt;
+
+    /// A copy of the transcript in its current state, separated from it by `label`.
+    /// Messages appended to the fork do not affect this transcript.
+    fn fork(&self, label: &[u8]) -> Self {
+        let mut fork = self.clone();
+        fork.append_bytes(label);
+        fork
+    }
+}
+
+impl<C> Transcript<C> for PoseidonTranscript<C>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    C::Base: PrimeField<Repr = [u8; 32]>,
+{
+    // The length, then 31 bytes per scalar, which is below the modulus of any 256-bit field
+    fn append_bytes(&mut self, bytes: &[u8]) {
+        PoseidonTranscript::append_scalar(self, &C::ScalarExt::from(bytes.len() as u64));
+        for chunk in bytes.chunks(31) {
+            let mut repr = [0u8; 32];
+            repr[..chunk.len()].copy_from_slice(chunk);
+            let scalar = C::ScalarExt::from_repr(repr).unwrap();
+            PoseidonTranscript::append_scalar(self, &scalar);
+        }
+    }
+
+    fn append_scalar(&mut self, scalar: &C::ScalarExt) {
+        PoseidonTranscript::append_scalar(self, scalar);
+    }
+
+    fn append_point(&mut self, point: &C) {
+        PoseidonTranscript::append_point(self, point);
+    }
+
+    fn append_points(&mut self, points: &[C]) {
+        PoseidonTranscript::append_points(self, points);
+    }
+
+    fn squeeze_challenge(&mut self) -> C::ScalarExt {
+        PoseidonTranscript::squeeze(self, 1)[0]
+    }
+}
+
+// Prefixes that keep messages of different kinds apart
+const BYTES: u8 = 0;
+const SCALAR: u8 = 1;
+const POINT: u8 = 2;
+const CHALLENGE: u8 = 3;
+
+/// A transcript over a hash function `D` with the `digest` interface.
+/// Challenges are the wide reduction of 64 bytes of output.
+#[derive(Clone)]
+pub struct HashTranscript<C, D> {
+    state: D,
+    _marker: PhantomData<C>,
+}
+
+/// A transcript over SHA3-256.
+pub type Sha3Transcript<C> = HashTranscript<C, Sha3_256>;
+
+/// A transcript over Keccak-256, as available to EVM verifiers.
+pub type KeccakTranscript<C> = HashTranscript<C, Keccak256>;
+
+impl<C, D> HashTranscript<C, D>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    D: Digest + Clone,
+{
+    pub fn new(label: &[u8]) -> Self {
+        let mut transcript = Self {
+            state: D::new(),
+            _marker: PhantomData,
+        };
+        transcript.append_bytes(label);
+        transcript
+    }
+}
+
+impl<C, D> Transcript<C> for HashTranscript<C, D>
+where
+    C: CurveAffineExt,
+    C::ScalarExt: PrimeFieldBits,
+    C::ScalarExt: PrimeField<Repr = [u8; 32]>,
+    C::ScalarExt: DefaultIsZeroes,
+    D: Digest + Clone,
+{
+    fn append_bytes(&mut self, bytes: &[u8]) {
+        self.state.input([BYTES]);
+        self.state.input((bytes.len() as u64).to_le_bytes());
+        self.state.input(bytes);
+    }
+
+    fn append_scalar(&mut self, scalar: &C::ScalarExt) {
+        self.state.input([SCALAR]);
+        self.state.input(scalar.to_repr());
+    }
+
+    fn append_point(&mut self, point: &C) {
+        self.state.input([POINT]);
+        self.state.input(point.to_bytes());
+    }
+
+    fn squeeze_challenge(&mut self) -> C::ScalarExt {
+        // Hash the state with a counter until there are 64 bytes to reduce
+        let mut bytes = Vec::with_capacity(64);
+        let mut counter = 0u8;
+        while bytes.len() < 64 {
+            let mut hasher = self.state.clone();
+            hasher.input([CHALLENGE, counter]);
+            bytes.extend_from_slice(&hasher.result());
+            counter += 1;
+        }
+        let mut wide = [0u8; 64];
+        wide.copy_from_slice(&bytes[..64]);
+        let challenge = C::ScalarExt::from_bytes_wide(&wide);
+
+        self.state.input([CHALLENGE]);
+        self.state.input(challenge.to_repr());
+        challenge
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves::secq256k1::Secq256k1Affine;
+    use halo2curves::CurveAffine;
+    use poseidon_transcript::sponge::SpongeCurve;
+
+    type C = Secq256k1Affine;
+
+    fn check_transcript<T: Transcript<C>>(new: impl Fn(&'static [u8]) -> T) {
+        let challenge = |transcript: &mut T| transcript.squeeze_challenge();
+
+        let mut base = new(b"test_transcript");
+        base.append_scalar(&<C as CurveAffine>::ScalarExt::from(5));
+        base.append_point(&C::generator());
+
+        // The same messages give the same challenges, and each squeeze a new one
+        let mut copy = base.clone();
+        let c1 = challenge(&mut base);
+        assert_eq!(c1, challenge(&mut copy));
+        assert!(challenge(&mut base) != c1);
+
+        assert!(challenge(&mut new(b"another_label")) != challenge(&mut new(b"test_transcript")));
+
+        // A fork depends on its label and leaves the original untouched
+        let mut fork_a = base.fork(b"a");
+        let mut fork_b = base.fork(b"b");
+        let mut snapshot = base.clone();
+        assert!(challenge(&mut fork_a) != challenge(&mut fork_b));
+        fork_a.append_bytes(b"more");
+        assert_eq!(challenge(&mut base), challenge(&mut snapshot));
+
+        // Bytes are length-prefixed
+        let mut split = new(b"test_transcript");
+        split.append_bytes(b"ab");
+        split.append_bytes(b"c");
+        let mut joined = new(b"test_transcript");
+        joined.append_bytes(b"abc");
+        assert!(challenge(&mut split) != challenge(&mut joined));
+    }
+
+    #[test]
+    fn test_poseidon_transcript() {
+        check_transcript(|label| PoseidonTranscript::<C>::new(label, SpongeCurve::K256));
+    }
+
+    #[test]
+    fn test_sha3_transcript() {
+        check_transcript(Sha3Transcript::<C>::new);
+    }
+
+    #[test]
+    fn test_keccak_transcript() {
+        check_transcript(KeccakTranscript::<C>::new);
+    }
+}
@@ -1,7 +1,25 @@
 use crate::CurveAffineExt;
-use ff::{Field, PrimeField, PrimeFieldBits};
+use ff::{PrimeField, PrimeFieldBits};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use zeroize::DefaultIsZeroes;
 
+/// `(0..n).map(f)` collected in order, spread across threads with the `parallel` feature.
+pub fn par_map<T, F>(n: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    {
+        (0..n).into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (0..n).map(f).collect()
+    }
+}
+
 pub fn hadamard_prod<C>(a: &[C::ScalarExt], b: &[C::ScalarExt]) -> Vec<C::ScalarExt>
 where
     C: CurveAffineExt,
@@ -10,9 +28,5 @@ where
     C::ScalarExt: DefaultIsZeroes,
 {
     assert_eq!(a.len(), b.len());
-    let mut result = vec![C::ScalarExt::zero(); a.len()];
-    for i in 0..a.len() {
-        result[i] = a[i] * b[i];
-    }
-    result
+    par_map(a.len(), |i| a[i] * b[i])
 }